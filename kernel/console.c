@@ -21,6 +21,7 @@
 #include "riscv.h"
 #include "defs.h"
 #include "proc.h"
+#include "errno.h"
 
 #define BACKSPACE 0x100
 #define C(x)  ((x)-'@')  // Control-x
@@ -98,7 +99,7 @@ consoleread(struct file *f, int user_dst, uint64 dst, int n)
     while(cons.r == cons.w){
       if(myproc()->killed){
         release(&cons.lock);
-        return -1;
+        return -EINTR;
       }
       sleep(&cons.r, &cons.lock);
     }
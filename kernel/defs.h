@@ -63,6 +63,7 @@ void            ramdiskrw(struct buf*);
 void*           kalloc(void);
 void            kfree(void *);
 void            kinit(void);
+void            kref_inc(void *);
 
 // log.c
 void            initlog(int, struct superblock*);
@@ -166,11 +167,16 @@ int             uvmcopy(pagetable_t, pagetable_t, uint64);
 void            uvmfree(pagetable_t, uint64);
 void            uvmunmap(pagetable_t, uint64, uint64, int);
 void            uvmclear(pagetable_t, uint64);
+pte_t *         walk(pagetable_t, uint64, int);
 uint64          walkaddr(pagetable_t, uint64);
 int             copyout(pagetable_t, uint64, char *, uint64);
 int             copyin(pagetable_t, char *, uint64, uint64);
 int             copyinstr(pagetable_t, char *, uint64, uint64);
 
+// vm.rs
+int             lazyalloc(pagetable_t, uint64);
+int             cowfault(pagetable_t, uint64);
+
 // plic.c
 void            plicinit(void);
 void            plicinithart(void);
@@ -187,23 +193,11 @@ void            virtio_disk_intr(void);
 
 // extra files for lab alloc
 
-// buddy.c
+// buddy.rs
 void           bd_init(void*,void*);
 void           bd_free(void*);
 void           *bd_malloc(uint64);
-
-struct list {
-  struct list *next;
-  struct list *prev;
-};
-
-// list.c
-void lst_init(struct list*);
-void lst_remove(struct list*);
-void lst_push(struct list*, void *);
-void *lst_pop(struct list*);
-void lst_print(struct list*);
-int lst_empty(struct list*);
+void           bd_print(void);
 
 // extra files for lab net
 
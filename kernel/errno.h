@@ -0,0 +1,31 @@
+// Error numbers returned by system calls.
+// A failing system call returns the negated error number,
+// e.g. open() of a missing file returns -ENOENT.
+// Keep in sync with osmium/xv6_defs/src/errno.rs.
+
+#define EPERM         1   // Operation not permitted
+#define ENOENT        2   // No such file or directory
+#define ESRCH         3   // No such process
+#define EINTR         4   // Interrupted system call (process killed)
+#define EIO           5   // I/O error
+#define ENXIO         6   // No such device
+#define E2BIG         7   // Argument list too long
+#define ENOEXEC       8   // Exec format error
+#define EBADF         9   // Bad file descriptor
+#define ECHILD       10   // No child processes
+#define EAGAIN       11   // Resource temporarily unavailable
+#define ENOMEM       12   // Out of memory
+#define EFAULT       14   // Bad address
+#define EEXIST       17   // File exists
+#define EXDEV        18   // Cross-device link
+#define ENOTDIR      20   // Not a directory
+#define EISDIR       21   // Is a directory
+#define EINVAL       22   // Invalid argument
+#define ENFILE       23   // File table overflow
+#define EMFILE       24   // Too many open files
+#define EFBIG        27   // File too large
+#define ENOSPC       28   // No space left on device
+#define EPIPE        32   // Broken pipe
+#define ENAMETOOLONG 36   // File name too long
+#define ENOSYS       38   // Function not implemented
+#define ENOTEMPTY    39   // Directory not empty
@@ -6,6 +6,7 @@
 #include "proc.h"
 #include "defs.h"
 #include "elf.h"
+#include "errno.h"
 
 static int loadseg(pde_t *pgdir, uint64 addr, struct inode *ip, uint offset, uint sz);
 
@@ -25,7 +26,7 @@ exec(char *path, char **argv)
 
   if((ip = namei(path)) == 0){
     end_op();
-    return -1;
+    return -ENOENT;
   }
   ilock(ip);
 
@@ -125,7 +126,7 @@ exec(char *path, char **argv)
     iunlockput(ip);
     end_op();
   }
-  return -1;
+  return -ENOEXEC;
 }
 
 // Load a program segment into pagetable at virtual address va.
@@ -12,6 +12,7 @@
 #include "file.h"
 #include "stat.h"
 #include "proc.h"
+#include "errno.h"
 
 struct devsw devsw[NDEV];
 struct {
@@ -84,6 +85,7 @@ fileclose(struct file *f)
 
 // Get metadata about file f.
 // addr is a user virtual address, pointing to a struct stat.
+// Returns 0, or a negated errno.
 int
 filestat(struct file *f, uint64 addr)
 {
@@ -95,33 +97,36 @@ filestat(struct file *f, uint64 addr)
     stati(f->ip, &st);
     iunlock(f->ip);
     if(copyout(p->pagetable, addr, (char *)&st, sizeof(st)) < 0)
-      return -1;
+      return -EFAULT;
     return 0;
   }
-  return -1;
+  return -EINVAL;
 }
 
 // Read from file f.
 // addr is a user virtual address.
+// Returns the number of bytes read, or a negated errno.
 int
 fileread(struct file *f, uint64 addr, int n)
 {
   int r = 0;
 
   if(f->readable == 0)
-    return -1;
+    return -EBADF;
 
   if(f->type == FD_PIPE){
     r = piperead(f->pipe, addr, n);
   } else if(f->type == FD_DEVICE){
     if(f->major < 0 || f->major >= NDEV || !devsw[f->major].read)
-      return -1;
+      return -ENXIO;
     r = devsw[f->major].read(f, 1, addr, n);
   } else if(f->type == FD_INODE){
     ilock(f->ip);
     if((r = readi(f->ip, 1, addr, f->off, n)) > 0)
       f->off += r;
     iunlock(f->ip);
+    if(r < 0)
+      r = -EFAULT;
   } else {
     panic("fileread");
   }
@@ -131,19 +136,20 @@ fileread(struct file *f, uint64 addr, int n)
 
 // Write to file f.
 // addr is a user virtual address.
+// Returns n, or a negated errno.
 int
 filewrite(struct file *f, uint64 addr, int n)
 {
   int r, ret = 0;
 
   if(f->writable == 0)
-    return -1;
+    return -EBADF;
 
   if(f->type == FD_PIPE){
     ret = pipewrite(f->pipe, addr, n);
   } else if(f->type == FD_DEVICE){
     if(f->major < 0 || f->major >= NDEV || !devsw[f->major].write)
-      return -1;
+      return -ENXIO;
     ret = devsw[f->major].write(f, 1, addr, n);
   } else if(f->type == FD_INODE){
     // write a few blocks at a time to avoid exceeding
@@ -166,13 +172,17 @@ filewrite(struct file *f, uint64 addr, int n)
       iunlock(f->ip);
       end_op();
 
-      if(r != n1){
-        // error from writei
+      if(r < 0){
+        // error from writei: a bad offset, the file would be
+        // too large, the disk is full, or addr is bad.
+        // a short write goes around again to get its errno.
+        ret = r;
         break;
       }
       i += r;
     }
-    ret = (i == n ? n : -1);
+    if(i == n)
+      ret = n;
   } else {
     panic("filewrite");
   }
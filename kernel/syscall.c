@@ -6,6 +6,7 @@
 #include "proc.h"
 #include "syscall.h"
 #include "defs.h"
+#include "errno.h"
 
 // Fetch the uint64 at addr from the current process.
 int
@@ -145,6 +146,6 @@ syscall(void)
   } else {
     printf("%d %s: unknown sys call %d\n",
             p->pid, p->name, num);
-    p->trapframe->a0 = -1;
+    p->trapframe->a0 = -ENOSYS;
   }
 }
@@ -2,6 +2,8 @@
 // File-system system calls.
 // Mostly argument checking, since we don't trust
 // user code, and calls into file.c and fs.c.
+// Errors are reported to user space as negated errno
+// values (see errno.h).
 //
 
 #include "types.h"
@@ -15,6 +17,7 @@
 #include "sleeplock.h"
 #include "file.h"
 #include "fcntl.h"
+#include "errno.h"
 
 // Fetch the nth word-sized system call argument as a file descriptor
 // and return both the descriptor and the corresponding struct file.
@@ -59,9 +62,9 @@ sys_dup(void)
   int fd;
 
   if(argfd(0, 0, &f) < 0)
-    return -1;
+    return -EBADF;
   if((fd=fdalloc(f)) < 0)
-    return -1;
+    return -EMFILE;
   filedup(f);
   return fd;
 }
@@ -73,8 +76,10 @@ sys_read(void)
   int n;
   uint64 p;
 
-  if(argfd(0, 0, &f) < 0 || argint(2, &n) < 0 || argaddr(1, &p) < 0)
-    return -1;
+  if(argint(2, &n) < 0 || argaddr(1, &p) < 0)
+    return -EINVAL;
+  if(argfd(0, 0, &f) < 0)
+    return -EBADF;
   return fileread(f, p, n);
 }
 
@@ -85,8 +90,10 @@ sys_write(void)
   int n;
   uint64 p;
 
-  if(argfd(0, 0, &f) < 0 || argint(2, &n) < 0 || argaddr(1, &p) < 0)
-    return -1;
+  if(argint(2, &n) < 0 || argaddr(1, &p) < 0)
+    return -EINVAL;
+  if(argfd(0, 0, &f) < 0)
+    return -EBADF;
 
   return filewrite(f, p, n);
 }
@@ -98,7 +105,7 @@ sys_close(void)
   struct file *f;
 
   if(argfd(0, &fd, &f) < 0)
-    return -1;
+    return -EBADF;
   myproc()->ofile[fd] = 0;
   fileclose(f);
   return 0;
@@ -110,8 +117,10 @@ sys_fstat(void)
   struct file *f;
   uint64 st; // user pointer to struct stat
 
-  if(argfd(0, 0, &f) < 0 || argaddr(1, &st) < 0)
-    return -1;
+  if(argaddr(1, &st) < 0)
+    return -EINVAL;
+  if(argfd(0, 0, &f) < 0)
+    return -EBADF;
   return filestat(f, st);
 }
 
@@ -121,32 +130,40 @@ sys_link(void)
 {
   char name[DIRSIZ], new[MAXPATH], old[MAXPATH];
   struct inode *dp, *ip;
+  int err;
 
   if(argstr(0, old, MAXPATH) < 0 || argstr(1, new, MAXPATH) < 0)
-    return -1;
+    return -EFAULT;
 
   begin_op();
   if((ip = namei(old)) == 0){
     end_op();
-    return -1;
+    return -ENOENT;
   }
 
   ilock(ip);
   if(ip->type == T_DIR){
     iunlockput(ip);
     end_op();
-    return -1;
+    return -EPERM;
   }
 
   ip->nlink++;
   iupdate(ip);
   iunlock(ip);
 
+  err = -ENOENT;
   if((dp = nameiparent(new, name)) == 0)
     goto bad;
   ilock(dp);
-  if(dp->dev != ip->dev || dirlink(dp, name, ip->inum) < 0){
+  if(dp->dev != ip->dev){
     iunlockput(dp);
+    err = -EXDEV;
+    goto bad;
+  }
+  if(dirlink(dp, name, ip->inum) < 0){
+    iunlockput(dp);
+    err = -EEXIST;
     goto bad;
   }
   iunlockput(dp);
@@ -162,7 +179,7 @@ bad:
   iupdate(ip);
   iunlockput(ip);
   end_op();
-  return -1;
+  return err;
 }
 
 // Is the directory dp empty except for "." and ".." ?
@@ -188,22 +205,25 @@ sys_unlink(void)
   struct dirent de;
   char name[DIRSIZ], path[MAXPATH];
   uint off;
+  int err;
 
   if(argstr(0, path, MAXPATH) < 0)
-    return -1;
+    return -EFAULT;
 
   begin_op();
   if((dp = nameiparent(path, name)) == 0){
     end_op();
-    return -1;
+    return -ENOENT;
   }
 
   ilock(dp);
 
   // Cannot unlink "." or "..".
+  err = -EINVAL;
   if(namecmp(name, ".") == 0 || namecmp(name, "..") == 0)
     goto bad;
 
+  err = -ENOENT;
   if((ip = dirlookup(dp, name, &off)) == 0)
     goto bad;
   ilock(ip);
@@ -212,6 +232,7 @@ sys_unlink(void)
     panic("unlink: nlink < 1");
   if(ip->type == T_DIR && !isdirempty(ip)){
     iunlockput(ip);
+    err = -ENOTEMPTY;
     goto bad;
   }
 
@@ -235,17 +256,22 @@ sys_unlink(void)
 bad:
   iunlockput(dp);
   end_op();
-  return -1;
+  return err;
 }
 
+// Create path as a new inode of the given type, or open the
+// existing file if type is T_FILE. On failure returns 0 and
+// sets *errp to the negated errno.
 static struct inode*
-create(char *path, short type, short major, short minor)
+create(char *path, short type, short major, short minor, int *errp)
 {
   struct inode *ip, *dp;
   char name[DIRSIZ];
 
-  if((dp = nameiparent(path, name)) == 0)
+  if((dp = nameiparent(path, name)) == 0){
+    *errp = -ENOENT;
     return 0;
+  }
 
   ilock(dp);
 
@@ -254,6 +280,7 @@ create(char *path, short type, short major, short minor)
     ilock(ip);
     if(type == T_FILE && (ip->type == T_FILE || ip->type == T_DEVICE))
       return ip;
+    *errp = (type == T_FILE) ? -EISDIR : -EEXIST;
     iunlockput(ip);
     return 0;
   }
@@ -290,44 +317,45 @@ sys_open(void)
   int fd, omode;
   struct file *f;
   struct inode *ip;
-  int n;
+  int n, err;
 
   if((n = argstr(0, path, MAXPATH)) < 0 || argint(1, &omode) < 0)
-    return -1;
+    return -EFAULT;
 
   begin_op();
 
   if(omode & O_CREATE){
-    ip = create(path, T_FILE, 0, 0);
+    ip = create(path, T_FILE, 0, 0, &err);
     if(ip == 0){
       end_op();
-      return -1;
+      return err;
     }
   } else {
     if((ip = namei(path)) == 0){
       end_op();
-      return -1;
+      return -ENOENT;
     }
     ilock(ip);
     if(ip->type == T_DIR && omode != O_RDONLY){
       iunlockput(ip);
       end_op();
-      return -1;
+      return -EISDIR;
     }
   }
 
   if(ip->type == T_DEVICE && (ip->major < 0 || ip->major >= NDEV)){
     iunlockput(ip);
     end_op();
-    return -1;
+    return -ENXIO;
   }
 
   if((f = filealloc()) == 0 || (fd = fdalloc(f)) < 0){
+    err = f ? -EMFILE : -ENFILE;
     if(f)
       fileclose(f);
     iunlockput(ip);
     end_op();
-    return -1;
+    return err;
   }
 
   if(ip->type == T_DEVICE){
@@ -357,11 +385,14 @@ sys_mkdir(void)
 {
   char path[MAXPATH];
   struct inode *ip;
+  int err;
 
+  if(argstr(0, path, MAXPATH) < 0)
+    return -EFAULT;
   begin_op();
-  if(argstr(0, path, MAXPATH) < 0 || (ip = create(path, T_DIR, 0, 0)) == 0){
+  if((ip = create(path, T_DIR, 0, 0, &err)) == 0){
     end_op();
-    return -1;
+    return err;
   }
   iunlockput(ip);
   end_op();
@@ -373,15 +404,16 @@ sys_mknod(void)
 {
   struct inode *ip;
   char path[MAXPATH];
-  int major, minor;
+  int major, minor, err;
 
-  begin_op();
   if((argstr(0, path, MAXPATH)) < 0 ||
      argint(1, &major) < 0 ||
-     argint(2, &minor) < 0 ||
-     (ip = create(path, T_DEVICE, major, minor)) == 0){
+     argint(2, &minor) < 0)
+    return -EFAULT;
+  begin_op();
+  if((ip = create(path, T_DEVICE, major, minor, &err)) == 0){
     end_op();
-    return -1;
+    return err;
   }
   iunlockput(ip);
   end_op();
@@ -395,16 +427,18 @@ sys_chdir(void)
   struct inode *ip;
   struct proc *p = myproc();
   
+  if(argstr(0, path, MAXPATH) < 0)
+    return -EFAULT;
   begin_op();
-  if(argstr(0, path, MAXPATH) < 0 || (ip = namei(path)) == 0){
+  if((ip = namei(path)) == 0){
     end_op();
-    return -1;
+    return -ENOENT;
   }
   ilock(ip);
   if(ip->type != T_DIR){
     iunlockput(ip);
     end_op();
-    return -1;
+    return -ENOTDIR;
   }
   iunlock(ip);
   iput(p->cwd);
@@ -417,18 +451,20 @@ uint64
 sys_exec(void)
 {
   char path[MAXPATH], *argv[MAXARG];
-  int i;
+  int i, err;
   uint64 uargv, uarg;
 
   if(argstr(0, path, MAXPATH) < 0 || argaddr(1, &uargv) < 0){
-    return -1;
+    return -EFAULT;
   }
   memset(argv, 0, sizeof(argv));
   for(i=0;; i++){
     if(i >= NELEM(argv)){
+      err = -E2BIG;
       goto bad;
     }
     if(fetchaddr(uargv+sizeof(uint64)*i, (uint64*)&uarg) < 0){
+      err = -EFAULT;
       goto bad;
     }
     if(uarg == 0){
@@ -436,10 +472,14 @@ sys_exec(void)
       break;
     }
     argv[i] = kalloc();
-    if(argv[i] == 0)
+    if(argv[i] == 0){
+      err = -ENOMEM;
       goto bad;
-    if(fetchstr(uarg, argv[i], PGSIZE) < 0)
+    }
+    if(fetchstr(uarg, argv[i], PGSIZE) < 0){
+      err = -EFAULT;
       goto bad;
+    }
   }
 
   int ret = exec(path, argv);
@@ -452,7 +492,7 @@ sys_exec(void)
  bad:
   for(i = 0; i < NELEM(argv) && argv[i] != 0; i++)
     kfree(argv[i]);
-  return -1;
+  return err;
 }
 
 uint64
@@ -464,16 +504,16 @@ sys_pipe(void)
   struct proc *p = myproc();
 
   if(argaddr(0, &fdarray) < 0)
-    return -1;
+    return -EFAULT;
   if(pipealloc(&rf, &wf) < 0)
-    return -1;
+    return -ENFILE;
   fd0 = -1;
   if((fd0 = fdalloc(rf)) < 0 || (fd1 = fdalloc(wf)) < 0){
     if(fd0 >= 0)
       p->ofile[fd0] = 0;
     fileclose(rf);
     fileclose(wf);
-    return -1;
+    return -EMFILE;
   }
   if(copyout(p->pagetable, fdarray, (char*)&fd0, sizeof(fd0)) < 0 ||
      copyout(p->pagetable, fdarray+sizeof(fd0), (char *)&fd1, sizeof(fd1)) < 0){
@@ -481,8 +521,9 @@ sys_pipe(void)
     p->ofile[fd1] = 0;
     fileclose(rf);
     fileclose(wf);
-    return -1;
+    return -EFAULT;
   }
+
   return 0;
 }
 
@@ -68,6 +68,10 @@ usertrap(void)
     intr_on();
 
     syscall();
+  } else if((r_scause() == 13 || r_scause() == 15) && lazyalloc(p->pagetable, r_stval()) == 0){
+    // first touch of a heap page, which sbrk() left unallocated.
+  } else if(r_scause() == 15 && cowfault(p->pagetable, r_stval()) == 0){
+    // store to a copy-on-write page, which now has a copy the process can write.
   } else if((which_dev = devintr()) != 0){
     // ok
   } else {
@@ -0,0 +1,188 @@
+//! Looks inside an xv6 file system image, such as `fs.img` after running xv6.
+//!
+//! - `info` prints the super block, the log header and how many inodes and blocks are in use.
+//! - `ls [-R] [PATH]` lists a directory in the format of xv6's `ls`: name, type, inode number and
+//!   size.
+//! - `cat PATH` writes a file to standard output.
+//! - `extract PATH DEST` copies a file, or a directory and everything in it, out of the image.
+//! - `fsck` checks the image for consistency (see `mkfs::fsck`) and exits with status 1 if it
+//!   finds problems.
+//!
+//! Paths are relative to the root directory of the image.
+
+use mkfs::disk::OnDisk;
+use mkfs::fsck;
+use mkfs::image::{dirent_name, Image};
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process;
+use xv6_defs::fs::*;
+use xv6_defs::stat::*;
+
+const USAGE: &str = "Usage: fsimg fs.img info
+       fsimg fs.img ls [-R] [path]
+       fsimg fs.img cat path
+       fsimg fs.img extract path dest
+       fsimg fs.img fsck";
+
+/// Prints `msg` and exits with an error.
+fn fail(msg: &str) -> ! {
+    eprintln!("fsimg: {}", msg);
+    process::exit(1)
+}
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(1)
+}
+
+/// Returns the inode number of `path`, or exits if it doesn't exist.
+fn namei(image: &Image, path: &str) -> u32 {
+    image
+        .namei(path)
+        .unwrap_or_else(|| fail(&format!("{}: no such file or directory", path)))
+}
+
+/// Returns the entries of directory `dir` that are in use, with their names, skipping `.` and
+/// `..` if `skip_dots` is set.
+fn entries(image: &Image, dir: u32, skip_dots: bool) -> Vec<(String, u32)> {
+    image
+        .dirents(dir)
+        .iter()
+        .filter(|de| de.inum != 0)
+        .map(|de| {
+            let name = String::from_utf8_lossy(dirent_name(de)).into_owned();
+            (name, de.inum as u32)
+        })
+        .filter(|(name, _)| !skip_dots || (name != "." && name != ".."))
+        .collect()
+}
+
+fn info(image: &Image) {
+    let sb = image.superblock();
+    println!("magic      {:#x}", sb.magic);
+    println!("size       {} blocks", sb.size);
+    println!(
+        "nblocks    {} data blocks from block {}",
+        sb.nblocks,
+        image.nmeta()
+    );
+    println!("ninodes    {}", sb.ninodes);
+    println!("nlog       {} blocks from block {}", sb.nlog, sb.logstart);
+    println!("inodestart {}", sb.inodestart);
+    println!("bmapstart  {}", sb.bmapstart);
+
+    let lh = LogHeader::read(image.get_block(sb.logstart).unwrap());
+    let n = lh.n.clamp(0, lh.block.len() as i32) as usize;
+    println!("log        {} blocks {:?}", lh.n, &lh.block[..n]);
+
+    let inodes = (1..sb.ninodes)
+        .filter(|&inum| image.rinode(inum).typ != 0)
+        .count();
+    let blocks = (image.nmeta()..sb.size)
+        .filter(|&b| image.bitmap_bit(b))
+        .count();
+    println!("in use     {} of {} inodes", inodes, sb.ninodes - 1);
+    println!("in use     {} of {} data blocks", blocks, sb.nblocks);
+}
+
+fn print_entry(image: &Image, name: &str, inum: u32) {
+    let din = image.rinode(inum);
+    println!(
+        "{:<width$} {} {} {}",
+        name,
+        din.typ,
+        inum,
+        din.size,
+        width = DIRSIZ
+    );
+}
+
+fn ls(image: &Image, path: &str, recursive: bool) {
+    let inum = namei(image, path);
+    if image.rinode(inum).typ != T_DIR {
+        print_entry(image, path, inum);
+        return;
+    }
+    let entries = entries(image, inum, false);
+    for (name, inum) in &entries {
+        if image.inode(*inum).is_some() {
+            print_entry(image, name, *inum);
+        } else {
+            println!("{:<width$} ? {} ?", name, inum, width = DIRSIZ);
+        }
+    }
+    if recursive {
+        for (name, inum) in entries {
+            if name == "." || name == ".." {
+                continue;
+            }
+            if image.inode(inum).map(|din| din.typ) == Some(T_DIR) {
+                let path = format!("{}/{}", path.trim_end_matches('/'), name);
+                println!("\n{}:", path);
+                ls(image, &path, true);
+            }
+        }
+    }
+}
+
+fn extract(image: &Image, inum: u32, dest: &Path) {
+    let din = image.rinode(inum);
+    let result = match din.typ {
+        T_FILE => fs::write(dest, image.read_inode(&din)),
+        T_DIR => fs::create_dir_all(dest).map(|()| {
+            for (name, inum) in entries(image, inum, true) {
+                if image.inode(inum).is_some() {
+                    extract(image, inum, &dest.join(name));
+                }
+            }
+        }),
+        typ => {
+            eprintln!("fsimg: skipping {}: type {}", dest.display(), typ);
+            Ok(())
+        }
+    };
+    if let Err(e) = result {
+        fail(&format!("{}: {}", dest.display(), e));
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.len() < 2 {
+        usage();
+    }
+    let data = fs::read(&args[0]).unwrap_or_else(|e| fail(&format!("{}: {}", args[0], e)));
+    let image = Image::open(data).unwrap_or_else(|e| fail(&format!("{}: {}", args[0], e)));
+
+    let rest: Vec<&str> = args[2..].iter().map(String::as_str).collect();
+    match (args[1].as_str(), rest.as_slice()) {
+        ("info", []) => info(&image),
+        ("ls", []) => ls(&image, "/", false),
+        ("ls", ["-R"]) => ls(&image, "/", true),
+        ("ls", [path]) => ls(&image, path, false),
+        ("ls", ["-R", path]) => ls(&image, path, true),
+        ("cat", [path]) => {
+            let inum = namei(&image, path);
+            if image.rinode(inum).typ == T_DIR {
+                fail(&format!("{}: is a directory", path));
+            }
+            let _ = io::stdout().write_all(&image.read(inum));
+        }
+        ("extract", [path, dest]) => extract(&image, namei(&image, path), Path::new(dest)),
+        ("fsck", []) => {
+            let problems = fsck::check(&image);
+            for problem in &problems {
+                println!("{}", problem);
+            }
+            if !problems.is_empty() {
+                println!("{}: {} problems", args[0], problems.len());
+                process::exit(1);
+            }
+            println!("{}: clean", args[0]);
+        }
+        _ => usage(),
+    }
+}
@@ -0,0 +1,147 @@
+//! Conversion of the on-disk structures in `xv6_defs::fs` to and from the bytes stored in an
+//! image. Fields are little endian on disk whatever the byte order of the build machine.
+
+use std::convert::TryInto;
+use std::mem;
+use xv6_defs::fs::*;
+use xv6_defs::param::LOGSIZE;
+
+/// A structure stored on disk as a sequence of little endian fields.
+pub trait OnDisk: Sized {
+    /// Size of the structure on disk, which is also its size in memory.
+    const SIZE: usize;
+
+    /// Decodes the structure from the start of `buf`.
+    fn read(buf: &[u8]) -> Self;
+
+    /// Encodes the structure into the start of `buf`.
+    fn write(&self, buf: &mut [u8]);
+}
+
+fn get_u16(buf: &[u8], off: usize) -> u16 {
+    u16::from_le_bytes(buf[off..off + 2].try_into().unwrap())
+}
+
+fn get_u32(buf: &[u8], off: usize) -> u32 {
+    u32::from_le_bytes(buf[off..off + 4].try_into().unwrap())
+}
+
+fn get_i32(buf: &[u8], off: usize) -> i32 {
+    get_u32(buf, off) as i32
+}
+
+fn put_u16(buf: &mut [u8], off: usize, x: u16) {
+    buf[off..off + 2].copy_from_slice(&x.to_le_bytes());
+}
+
+fn put_u32(buf: &mut [u8], off: usize, x: u32) {
+    buf[off..off + 4].copy_from_slice(&x.to_le_bytes());
+}
+
+impl OnDisk for SuperBlock {
+    const SIZE: usize = mem::size_of::<SuperBlock>();
+
+    fn read(buf: &[u8]) -> Self {
+        SuperBlock {
+            magic: get_u32(buf, 0),
+            size: get_u32(buf, 4),
+            nblocks: get_u32(buf, 8),
+            ninodes: get_u32(buf, 12),
+            nlog: get_u32(buf, 16),
+            logstart: get_u32(buf, 20),
+            inodestart: get_u32(buf, 24),
+            bmapstart: get_u32(buf, 28),
+        }
+    }
+
+    fn write(&self, buf: &mut [u8]) {
+        put_u32(buf, 0, self.magic);
+        put_u32(buf, 4, self.size);
+        put_u32(buf, 8, self.nblocks);
+        put_u32(buf, 12, self.ninodes);
+        put_u32(buf, 16, self.nlog);
+        put_u32(buf, 20, self.logstart);
+        put_u32(buf, 24, self.inodestart);
+        put_u32(buf, 28, self.bmapstart);
+    }
+}
+
+impl OnDisk for DInode {
+    const SIZE: usize = mem::size_of::<DInode>();
+
+    fn read(buf: &[u8]) -> Self {
+        let mut addrs = [0; NDIRECT + 1];
+        for (i, addr) in addrs.iter_mut().enumerate() {
+            *addr = get_u32(buf, 12 + 4 * i);
+        }
+        DInode {
+            typ: get_u16(buf, 0) as i16,
+            major: get_u16(buf, 2) as i16,
+            minor: get_u16(buf, 4) as i16,
+            nlink: get_u16(buf, 6) as i16,
+            size: get_u32(buf, 8),
+            addrs,
+        }
+    }
+
+    fn write(&self, buf: &mut [u8]) {
+        put_u16(buf, 0, self.typ as u16);
+        put_u16(buf, 2, self.major as u16);
+        put_u16(buf, 4, self.minor as u16);
+        put_u16(buf, 6, self.nlink as u16);
+        put_u32(buf, 8, self.size);
+        for (i, &addr) in self.addrs.iter().enumerate() {
+            put_u32(buf, 12 + 4 * i, addr);
+        }
+    }
+}
+
+impl OnDisk for Dirent {
+    const SIZE: usize = mem::size_of::<Dirent>();
+
+    fn read(buf: &[u8]) -> Self {
+        let mut name = [0; DIRSIZ];
+        name.copy_from_slice(&buf[2..2 + DIRSIZ]);
+        Dirent {
+            inum: get_u16(buf, 0),
+            name,
+        }
+    }
+
+    fn write(&self, buf: &mut [u8]) {
+        put_u16(buf, 0, self.inum);
+        buf[2..2 + DIRSIZ].copy_from_slice(&self.name);
+    }
+}
+
+impl OnDisk for LogHeader {
+    const SIZE: usize = mem::size_of::<LogHeader>();
+
+    fn read(buf: &[u8]) -> Self {
+        let mut block = [0; LOGSIZE];
+        for (i, b) in block.iter_mut().enumerate() {
+            *b = get_i32(buf, 4 + 4 * i);
+        }
+        LogHeader {
+            n: get_i32(buf, 0),
+            block,
+        }
+    }
+
+    fn write(&self, buf: &mut [u8]) {
+        put_u32(buf, 0, self.n as u32);
+        for (i, &b) in self.block.iter().enumerate() {
+            put_u32(buf, 4 + 4 * i, b as u32);
+        }
+    }
+}
+
+/// Reads the `i`th block address from an indirect block.
+pub fn get_addr(block: &[u8], i: usize) -> u32 {
+    get_u32(block, 4 * i)
+}
+
+/// Writes the `i`th block address of an indirect block.
+pub fn put_addr(block: &mut [u8], i: usize, addr: u32) {
+    put_u32(block, 4 * i, addr);
+}
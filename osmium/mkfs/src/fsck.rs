@@ -0,0 +1,577 @@
+//! Consistency checks for a file system image, in the spirit of Unix fsck.
+//!
+//! `check` compares what the image says with what it should say given the rest of it:
+//!
+//! - the log header doesn't hold a committed transaction that hasn't been installed yet,
+//! - every allocated inode has a known type, a possible size and data blocks inside the data
+//!   area, and no data block belongs to two inodes (or twice to one),
+//! - the directory tree starting at the root is well formed, only refers to allocated inodes and
+//!   reaches every allocated inode,
+//! - each inode's `nlink` matches the number of directory entries naming it, counting the way the
+//!   kernel does: a directory is linked from its parent and from the `..` of each subdirectory,
+//!   and the root counts its own `..`,
+//! - the bitmap marks exactly the metadata blocks and the blocks in use as allocated.
+//!
+//! Nothing is repaired; the problems are returned for the caller to report.
+
+use crate::disk::{self, OnDisk};
+use crate::image::{dirent_name, Image};
+use std::collections::HashMap;
+use std::fmt;
+use xv6_defs::c_types::*;
+use xv6_defs::fs::*;
+use xv6_defs::param::LOGSIZE;
+use xv6_defs::stat::*;
+
+/// An inconsistency found by `check`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Problem {
+    /// The log header's count is negative or larger than the log.
+    BadLogHeader {
+        n: c_int,
+    },
+    /// The log holds `n` blocks of a committed transaction that hasn't been installed. The kernel
+    /// replays it on the next boot, so this is only a problem if the image is used elsewhere.
+    UninstalledLog {
+        n: c_int,
+    },
+    /// Entry `i` of the log header names a block that can't be logged.
+    BadLogBlock {
+        i: usize,
+        block: c_int,
+    },
+    BadType {
+        inum: u32,
+        typ: c_short,
+    },
+    /// The size is larger than a file can be.
+    BadSize {
+        inum: u32,
+        size: c_uint,
+    },
+    /// A block number outside the data area.
+    BadBlock {
+        inum: u32,
+        block: u32,
+    },
+    /// A data block used by two inodes, or twice by one.
+    DuplicateBlock {
+        block: u32,
+        first: u32,
+        second: u32,
+    },
+    /// The root inode isn't a directory.
+    BadRoot {
+        typ: c_short,
+    },
+    /// A directory whose size isn't a whole number of entries.
+    BadDirSize {
+        path: String,
+        size: c_uint,
+    },
+    /// A directory whose `.` or `..` entry is missing or points to the wrong inode.
+    BadDotEntry {
+        path: String,
+        name: &'static str,
+        inum: Option<u32>,
+        expected: u32,
+    },
+    /// A directory entry naming an inode that doesn't exist or is free.
+    FreeInodeLinked {
+        path: String,
+        inum: u32,
+    },
+    /// A directory reachable by more than one path.
+    DirLinkedTwice {
+        path: String,
+        inum: u32,
+    },
+    /// An allocated inode that isn't in any directory.
+    Orphaned {
+        inum: u32,
+        nlink: c_short,
+    },
+    /// `nlink` doesn't match the number of directory entries naming the inode.
+    BadNlink {
+        inum: u32,
+        path: String,
+        nlink: c_short,
+        links: u32,
+    },
+    /// A block in use, by inode `inum` or as metadata if `None`, that the bitmap marks free.
+    UsedBlockFree {
+        block: u32,
+        inum: Option<u32>,
+    },
+    /// A block the bitmap marks in use that nothing uses.
+    LeakedBlock {
+        block: u32,
+    },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Problem::BadLogHeader { n } => write!(f, "log header has bad count {}", n),
+            Problem::UninstalledLog { n } => write!(
+                f,
+                "log holds {} committed blocks that haven't been installed",
+                n
+            ),
+            Problem::BadLogBlock { i, block } => {
+                write!(f, "log entry {} names bad block {}", i, block)
+            }
+            Problem::BadType { inum, typ } => write!(f, "inode {} has bad type {}", inum, typ),
+            Problem::BadSize { inum, size } => write!(f, "inode {} has bad size {}", inum, size),
+            Problem::BadBlock { inum, block } => write!(
+                f,
+                "inode {} uses block {} outside the data area",
+                inum, block
+            ),
+            Problem::DuplicateBlock {
+                block,
+                first,
+                second,
+            } => write!(
+                f,
+                "block {} used by both inode {} and inode {}",
+                block, first, second
+            ),
+            Problem::BadRoot { typ } => write!(f, "root inode has type {}, not a directory", typ),
+            Problem::BadDirSize { path, size } => {
+                write!(f, "directory {} has bad size {}", path, size)
+            }
+            Problem::BadDotEntry {
+                path,
+                name,
+                inum: Some(inum),
+                expected,
+            } => write!(
+                f,
+                "{} in {} is inode {}, not {}",
+                name, path, inum, expected
+            ),
+            Problem::BadDotEntry {
+                path,
+                name,
+                inum: None,
+                ..
+            } => write!(f, "directory {} has no {} entry", path, name),
+            Problem::FreeInodeLinked { path, inum } => {
+                write!(f, "{} refers to free inode {}", path, inum)
+            }
+            Problem::DirLinkedTwice { path, inum } => write!(
+                f,
+                "{} is directory {}, which is already linked elsewhere",
+                path, inum
+            ),
+            Problem::Orphaned { inum, nlink } => write!(
+                f,
+                "inode {} is allocated (nlink {}) but not in any directory",
+                inum, nlink
+            ),
+            Problem::BadNlink {
+                inum,
+                path,
+                nlink,
+                links,
+            } => write!(
+                f,
+                "{} (inode {}) has nlink {} but {} links",
+                path, inum, nlink, links
+            ),
+            Problem::UsedBlockFree {
+                block,
+                inum: Some(inum),
+            } => write!(
+                f,
+                "block {} used by inode {} is free in the bitmap",
+                block, inum
+            ),
+            Problem::UsedBlockFree { block, inum: None } => {
+                write!(f, "metadata block {} is free in the bitmap", block)
+            }
+            Problem::LeakedBlock { block } => {
+                write!(f, "block {} is in use in the bitmap but unused", block)
+            }
+        }
+    }
+}
+
+/// Checks `image` and returns the problems found, in the order the checks run.
+pub fn check(image: &Image) -> Vec<Problem> {
+    let mut checker = Checker {
+        image,
+        problems: Vec::new(),
+        owners: HashMap::new(),
+        links: vec![0; image.superblock().ninodes as usize],
+        paths: HashMap::new(),
+    };
+    checker.check_log();
+    checker.check_inodes();
+    checker.check_tree();
+    checker.check_links();
+    checker.check_bitmap();
+    checker.problems
+}
+
+struct Checker<'a> {
+    image: &'a Image,
+    problems: Vec<Problem>,
+    /// The inode using each data block.
+    owners: HashMap<u32, u32>,
+    /// Number of directory entries naming each inode.
+    links: Vec<u32>,
+    /// A path for each inode reached from the root, for messages.
+    paths: HashMap<u32, String>,
+}
+
+impl Checker<'_> {
+    fn check_log(&mut self) {
+        let sb = self.image.superblock();
+        let lh = LogHeader::read(self.image.get_block(sb.logstart).unwrap());
+        if lh.n < 0 || lh.n as usize > LOGSIZE || lh.n as u32 >= sb.nlog {
+            self.problems.push(Problem::BadLogHeader { n: lh.n });
+            return;
+        }
+        if lh.n > 0 {
+            self.problems.push(Problem::UninstalledLog { n: lh.n });
+        }
+        for (i, &block) in lh.block[..lh.n as usize].iter().enumerate() {
+            // Only blocks past the log can be logged.
+            if block < (sb.logstart + sb.nlog) as c_int || block as u32 >= sb.size {
+                self.problems.push(Problem::BadLogBlock { i, block });
+            }
+        }
+    }
+
+    /// Returns whether inode `inum` exists and is allocated.
+    fn allocated(&self, inum: u32) -> bool {
+        match self.image.inode(inum) {
+            Some(din) => din.typ != 0,
+            None => false,
+        }
+    }
+
+    fn check_inodes(&mut self) {
+        for inum in 1..self.image.superblock().ninodes {
+            let din = self.image.rinode(inum);
+            match din.typ {
+                0 => continue,
+                T_DIR | T_FILE | T_DEVICE => {}
+                typ => {
+                    self.problems.push(Problem::BadType { inum, typ });
+                    continue;
+                }
+            }
+            if din.size as usize > MAXFILE * BSIZE {
+                self.problems.push(Problem::BadSize {
+                    inum,
+                    size: din.size,
+                });
+            }
+            for &block in &din.addrs[..NDIRECT] {
+                self.use_block(inum, block);
+            }
+            let indirect = din.addrs[NDIRECT];
+            if self.use_block(inum, indirect) {
+                let block = self.image.get_block(indirect).unwrap();
+                for i in 0..NINDIRECT {
+                    self.use_block(inum, disk::get_addr(block, i));
+                }
+            }
+        }
+    }
+
+    /// Records that `inum` uses `block`, unless it's 0 for no block. Returns whether the block
+    /// is a data block.
+    fn use_block(&mut self, inum: u32, block: u32) -> bool {
+        if block == 0 {
+            return false;
+        }
+        if block < self.image.nmeta() || block >= self.image.superblock().size {
+            self.problems.push(Problem::BadBlock { inum, block });
+            return false;
+        }
+        if let Some(&first) = self.owners.get(&block) {
+            self.problems.push(Problem::DuplicateBlock {
+                block,
+                first,
+                second: inum,
+            });
+        } else {
+            self.owners.insert(block, inum);
+        }
+        true
+    }
+
+    fn check_tree(&mut self) {
+        let root = self.image.rinode(ROOTINO);
+        if root.typ != T_DIR {
+            self.problems.push(Problem::BadRoot { typ: root.typ });
+            return;
+        }
+        self.paths.insert(ROOTINO, "/".to_string());
+        // Directories to check, with their parents.
+        let mut dirs = vec![(ROOTINO, ROOTINO)];
+        while let Some((dir, parent)) = dirs.pop() {
+            let path = self.paths[&dir].clone();
+            let din = self.image.rinode(dir);
+            if !(din.size as usize).is_multiple_of(Dirent::SIZE) {
+                self.problems.push(Problem::BadDirSize {
+                    path: path.clone(),
+                    size: din.size,
+                });
+            }
+            let mut dot = None;
+            let mut dotdot = None;
+            for de in self.image.dirents(dir) {
+                let inum = de.inum as u32;
+                if inum == 0 {
+                    continue;
+                }
+                let name = String::from_utf8_lossy(dirent_name(&de)).into_owned();
+                match name.as_str() {
+                    "." => dot = dot.or(Some(inum)),
+                    ".." => dotdot = dotdot.or(Some(inum)),
+                    _ => {}
+                }
+                let child_path = match path.as_str() {
+                    "/" => format!("/{}", name),
+                    _ => format!("{}/{}", path, name),
+                };
+                if !self.allocated(inum) {
+                    self.problems.push(Problem::FreeInodeLinked {
+                        path: child_path,
+                        inum,
+                    });
+                    continue;
+                }
+                // "." isn't counted, to avoid a cyclic reference count.
+                if name != "." {
+                    self.links[inum as usize] += 1;
+                }
+                if name == "." || name == ".." {
+                    continue;
+                }
+                if self.paths.contains_key(&inum) {
+                    if self.image.rinode(inum).typ == T_DIR {
+                        self.problems.push(Problem::DirLinkedTwice {
+                            path: child_path,
+                            inum,
+                        });
+                    }
+                    continue;
+                }
+                self.paths.insert(inum, child_path);
+                if self.image.rinode(inum).typ == T_DIR {
+                    dirs.push((inum, dir));
+                }
+            }
+            for (name, inum, expected) in [(".", dot, dir), ("..", dotdot, parent)] {
+                if inum != Some(expected) {
+                    self.problems.push(Problem::BadDotEntry {
+                        path: path.clone(),
+                        name,
+                        inum,
+                        expected,
+                    });
+                }
+            }
+        }
+    }
+
+    fn check_links(&mut self) {
+        for inum in 1..self.image.superblock().ninodes {
+            let din = self.image.rinode(inum);
+            if !matches!(din.typ, T_DIR | T_FILE | T_DEVICE) {
+                continue;
+            }
+            match self.paths.get(&inum) {
+                None => self.problems.push(Problem::Orphaned {
+                    inum,
+                    nlink: din.nlink,
+                }),
+                Some(path) => {
+                    let links = self.links[inum as usize];
+                    if din.nlink as i64 != links as i64 {
+                        self.problems.push(Problem::BadNlink {
+                            inum,
+                            path: path.clone(),
+                            nlink: din.nlink,
+                            links,
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    fn check_bitmap(&mut self) {
+        for block in 0..self.image.superblock().size {
+            let owner = self.owners.get(&block).copied();
+            let used = block < self.image.nmeta() || owner.is_some();
+            match (used, self.image.bitmap_bit(block)) {
+                (true, false) => self
+                    .problems
+                    .push(Problem::UsedBlockFree { block, inum: owner }),
+                (false, true) => self.problems.push(Problem::LeakedBlock { block }),
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns a fresh image with a file in a subdirectory and a file with an indirect block.
+    fn image() -> Image {
+        let mut image = Image::new(2000, 200).unwrap();
+        let dir = image.mkdir_all("/etc").unwrap();
+        image.create_file(dir, "motd", b"hello").unwrap();
+        image
+            .create_file(ROOTINO, "big", &[7; (NDIRECT + 2) * BSIZE])
+            .unwrap();
+        Image::open(image.finish()).unwrap()
+    }
+
+    /// Applies `f` to the raw bytes of `image` and reopens it.
+    fn corrupt(image: Image, f: impl FnOnce(&Image, &mut Vec<u8>)) -> Image {
+        let mut data = image.as_bytes().to_vec();
+        f(&image, &mut data);
+        Image::open(data).unwrap()
+    }
+
+    fn inode_offset(image: &Image, inum: u32) -> usize {
+        image.superblock().iblock(inum) as usize * BSIZE + inum as usize % IPB * DInode::SIZE
+    }
+
+    fn write_inode(image: &Image, data: &mut [u8], inum: u32, f: impl FnOnce(&mut DInode)) {
+        let off = inode_offset(image, inum);
+        let mut din = DInode::read(&data[off..]);
+        f(&mut din);
+        din.write(&mut data[off..]);
+    }
+
+    #[test]
+    fn fresh_image_is_clean() {
+        assert_eq!(check(&image()), []);
+    }
+
+    #[test]
+    fn uninstalled_log() {
+        let image = corrupt(image(), |image, data| {
+            let lh = LogHeader {
+                n: 1,
+                block: [image.nmeta() as c_int; LOGSIZE],
+            };
+            lh.write(&mut data[image.superblock().logstart as usize * BSIZE..]);
+        });
+        assert_eq!(check(&image), [Problem::UninstalledLog { n: 1 }]);
+    }
+
+    #[test]
+    fn duplicate_block_and_bad_block() {
+        let image = image();
+        let big = image.namei("/big").unwrap();
+        let motd = image.namei("/etc/motd").unwrap();
+        let shared = image.rinode(big).addrs[0];
+        let image = corrupt(image, |image, data| {
+            write_inode(image, data, motd, |din| din.addrs[1] = shared);
+            write_inode(image, data, big, |din| din.addrs[2] = 3);
+        });
+        assert_eq!(
+            check(&image),
+            [
+                Problem::DuplicateBlock {
+                    block: shared,
+                    first: motd,
+                    second: big
+                },
+                Problem::BadBlock {
+                    inum: big,
+                    block: 3
+                },
+                // The block big no longer uses.
+                Problem::LeakedBlock { block: shared + 2 },
+            ]
+        );
+    }
+
+    #[test]
+    fn orphan_and_nlink() {
+        let image = image();
+        let motd = image.namei("/etc/motd").unwrap();
+        let etc = image.namei("/etc").unwrap();
+        let image = corrupt(image, |image, data| {
+            write_inode(image, data, motd, |din| din.nlink = 2);
+            write_inode(image, data, 50, |din| {
+                din.typ = T_FILE;
+                din.nlink = 1;
+            });
+            write_inode(image, data, etc, |din| din.nlink = 2);
+        });
+        assert_eq!(
+            check(&image),
+            [
+                Problem::BadNlink {
+                    inum: etc,
+                    path: "/etc".to_string(),
+                    nlink: 2,
+                    links: 1
+                },
+                Problem::BadNlink {
+                    inum: motd,
+                    path: "/etc/motd".to_string(),
+                    nlink: 2,
+                    links: 1
+                },
+                Problem::Orphaned { inum: 50, nlink: 1 },
+            ]
+        );
+    }
+
+    #[test]
+    fn free_inode_in_directory() {
+        let image = image();
+        let motd = image.namei("/etc/motd").unwrap();
+        let image = corrupt(image, |image, data| {
+            write_inode(image, data, motd, |din| *din = DInode::default());
+        });
+        let problems = check(&image);
+        assert_eq!(
+            problems[0],
+            Problem::FreeInodeLinked {
+                path: "/etc/motd".to_string(),
+                inum: motd
+            }
+        );
+        // Its block is still marked in use.
+        assert!(matches!(problems[1], Problem::LeakedBlock { .. }));
+    }
+
+    #[test]
+    fn bitmap_mismatch() {
+        let image = image();
+        let motd = image.namei("/etc/motd").unwrap();
+        let block = image.rinode(motd).addrs[0];
+        let free = image.superblock().size - 1;
+        let image = corrupt(image, |image, data| {
+            let bmap = image.superblock().bmapstart as usize * BSIZE;
+            data[bmap + block as usize / 8] &= !(1 << (block % 8));
+            data[bmap + free as usize / 8] |= 1 << (free % 8);
+        });
+        assert_eq!(
+            check(&image),
+            [
+                Problem::UsedBlockFree {
+                    block,
+                    inum: Some(motd)
+                },
+                Problem::LeakedBlock { block: free },
+            ]
+        );
+    }
+}
@@ -0,0 +1,517 @@
+//! A file system image held in memory, either under construction by mkfs or read from a file to
+//! be inspected.
+//!
+//! Inodes and blocks are allocated in order, like `mkfs/mkfs.c`: inode numbers count up from
+//! `ROOTINO` and data blocks count up from the first block after the metadata. Nothing is ever
+//! freed, so the bitmap only needs to be written once everything has been added, by `finish`.
+//!
+//! Reading doesn't trust the image: block and inode numbers that are out of range read as zeros
+//! or `None`, so a corrupt image can still be looked at. `fsck` reports such problems.
+
+use crate::disk::{self, OnDisk};
+use std::error;
+use std::fmt;
+use xv6_defs::c_types::*;
+use xv6_defs::fs::*;
+use xv6_defs::param::LOGSIZE;
+use xv6_defs::stat::*;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The image has no room for data blocks after its `nmeta` metadata blocks.
+    TooSmall {
+        size: u32,
+        nmeta: u32,
+    },
+    /// The super block doesn't start with `FSMAGIC`.
+    BadMagic(u32),
+    /// The super block describes a layout that doesn't fit in the image.
+    BadSuperBlock(SuperBlock),
+    OutOfInodes,
+    OutOfBlocks,
+    /// A file is longer than `MAXFILE` blocks.
+    FileTooLarge,
+    /// A name that can't be stored in a directory entry: empty, `.`, `..` or too long.
+    BadName(String),
+    AlreadyExists(String),
+    NotADirectory(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::TooSmall { size, nmeta } => write!(
+                f,
+                "{} blocks is too small for {} blocks of metadata",
+                size, nmeta
+            ),
+            Error::BadMagic(magic) => write!(
+                f,
+                "not an xv6 file system: magic {:#x} instead of {:#x}",
+                magic, FSMAGIC
+            ),
+            Error::BadSuperBlock(sb) => write!(f, "bad super block {:?}", sb),
+            Error::OutOfInodes => write!(f, "out of inodes"),
+            Error::OutOfBlocks => write!(f, "out of data blocks"),
+            Error::FileTooLarge => write!(f, "file larger than {} blocks", MAXFILE),
+            Error::BadName(name) => write!(
+                f,
+                "bad name '{}': names must be 1 to {} bytes and not . or ..",
+                name, DIRSIZ
+            ),
+            Error::AlreadyExists(name) => write!(f, "{} already exists", name),
+            Error::NotADirectory(name) => write!(f, "{} is not a directory", name),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+pub struct Image {
+    sb: SuperBlock,
+    /// Number of meta blocks (boot, sb, nlog, inode, bitmap).
+    nmeta: u32,
+    blocks: Vec<u8>,
+    /// Next inode number to allocate.
+    freeinode: u32,
+    /// The first free block that we can allocate.
+    freeblock: u32,
+}
+
+impl Image {
+    /// Returns an image of `size` blocks with room for `ninodes` inodes (including the unused
+    /// inode 0), containing an empty root directory.
+    pub fn new(size: u32, ninodes: u32) -> Result<Image> {
+        let nbitmap = size / BPB as u32 + 1;
+        let ninodeblocks = ninodes / IPB as u32 + 1;
+        let nlog = LOGSIZE as u32;
+        let nmeta = 2 + nlog + ninodeblocks + nbitmap;
+        if nmeta >= size {
+            return Err(Error::TooSmall { size, nmeta });
+        }
+
+        let sb = SuperBlock {
+            magic: FSMAGIC,
+            size,
+            nblocks: size - nmeta,
+            ninodes,
+            nlog,
+            logstart: 2,
+            inodestart: 2 + nlog,
+            bmapstart: 2 + nlog + ninodeblocks,
+        };
+        let mut image = Image {
+            sb,
+            nmeta,
+            blocks: vec![0; size as usize * BSIZE],
+            freeinode: 1,
+            freeblock: nmeta,
+        };
+        sb.write(image.block_mut(1));
+
+        let rootino = image.ialloc(T_DIR)?;
+        assert_eq!(rootino, ROOTINO);
+        image.append_dirent(rootino, ".", rootino)?;
+        image.append_dirent(rootino, "..", rootino)?;
+        Ok(image)
+    }
+
+    /// Returns the image stored in `data`, which must hold at least as many blocks as its super
+    /// block says. Nothing can be added to it.
+    pub fn open(mut data: Vec<u8>) -> Result<Image> {
+        if data.len() < 2 * BSIZE {
+            data.resize(2 * BSIZE, 0);
+        }
+        let sb = SuperBlock::read(&data[BSIZE..]);
+        if sb.magic != FSMAGIC {
+            return Err(Error::BadMagic(sb.magic));
+        }
+        let nblocks = data.len() / BSIZE;
+        let ninodeblocks = sb.ninodes as usize / IPB + 1;
+        let nmeta = sb.size.wrapping_sub(sb.nblocks);
+        if sb.size as usize > nblocks
+            || sb.nblocks > sb.size
+            || sb.logstart < 2
+            || sb.inodestart < sb.logstart + sb.nlog
+            || (sb.bmapstart as usize) < sb.inodestart as usize + ninodeblocks
+            || nmeta <= sb.bmapstart
+        {
+            return Err(Error::BadSuperBlock(sb));
+        }
+        data.truncate(sb.size as usize * BSIZE);
+        Ok(Image {
+            sb,
+            nmeta,
+            blocks: data,
+            freeinode: sb.ninodes,
+            freeblock: sb.size,
+        })
+    }
+
+    pub fn superblock(&self) -> &SuperBlock {
+        &self.sb
+    }
+
+    /// Returns the number of blocks before the data blocks, which is also the number of the first
+    /// data block.
+    pub fn nmeta(&self) -> u32 {
+        self.nmeta
+    }
+
+    /// Returns the number of blocks in use, counting all the metadata blocks.
+    pub fn used_blocks(&self) -> u32 {
+        self.freeblock
+    }
+
+    /// Returns the raw contents of the image.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.blocks
+    }
+
+    /// Returns block `b`, or `None` if it's past the end of the image.
+    pub fn get_block(&self, b: u32) -> Option<&[u8]> {
+        if b < self.sb.size {
+            Some(self.block(b))
+        } else {
+            None
+        }
+    }
+
+    /// Returns whether block `b` is marked in use in the bitmap.
+    pub fn bitmap_bit(&self, b: u32) -> bool {
+        let bit = b as usize % BPB;
+        match self.get_block(self.sb.bblock(b)) {
+            Some(block) => block[bit / 8] & (1 << (bit % 8)) != 0,
+            None => false,
+        }
+    }
+
+    fn block(&self, b: u32) -> &[u8] {
+        let start = b as usize * BSIZE;
+        &self.blocks[start..start + BSIZE]
+    }
+
+    fn block_mut(&mut self, b: u32) -> &mut [u8] {
+        let start = b as usize * BSIZE;
+        &mut self.blocks[start..start + BSIZE]
+    }
+
+    /// Returns the block holding inode `inum` and the offset of the inode in it.
+    fn inode_pos(&self, inum: u32) -> (u32, usize) {
+        (self.sb.iblock(inum), (inum as usize % IPB) * DInode::SIZE)
+    }
+
+    /// Returns inode `inum`, or `None` if there is no such inode.
+    pub fn inode(&self, inum: u32) -> Option<DInode> {
+        if inum == 0 || inum >= self.sb.ninodes {
+            None
+        } else {
+            Some(self.rinode(inum))
+        }
+    }
+
+    pub fn rinode(&self, inum: u32) -> DInode {
+        let (b, off) = self.inode_pos(inum);
+        DInode::read(&self.block(b)[off..])
+    }
+
+    fn winode(&mut self, inum: u32, din: &DInode) {
+        let (b, off) = self.inode_pos(inum);
+        din.write(&mut self.block_mut(b)[off..]);
+    }
+
+    /// Allocates a new inode of type `typ` with one link.
+    pub fn ialloc(&mut self, typ: c_short) -> Result<u32> {
+        if self.freeinode >= self.sb.ninodes {
+            return Err(Error::OutOfInodes);
+        }
+        let inum = self.freeinode;
+        self.freeinode += 1;
+        let din = DInode {
+            typ,
+            nlink: 1,
+            ..DInode::default()
+        };
+        self.winode(inum, &din);
+        Ok(inum)
+    }
+
+    /// Allocates the next data block.
+    fn balloc(&mut self) -> Result<u32> {
+        if self.freeblock >= self.sb.size {
+            return Err(Error::OutOfBlocks);
+        }
+        let b = self.freeblock;
+        self.freeblock += 1;
+        Ok(b)
+    }
+
+    /// Appends `data` to the contents of inode `inum`, allocating blocks as needed.
+    pub fn iappend(&mut self, inum: u32, mut data: &[u8]) -> Result<()> {
+        let mut din = self.rinode(inum);
+        let mut off = din.size as usize;
+        while !data.is_empty() {
+            let fbn = off / BSIZE;
+            if fbn >= MAXFILE {
+                return Err(Error::FileTooLarge);
+            }
+            let x = if fbn < NDIRECT {
+                if din.addrs[fbn] == 0 {
+                    din.addrs[fbn] = self.balloc()?;
+                }
+                din.addrs[fbn]
+            } else {
+                if din.addrs[NDIRECT] == 0 {
+                    din.addrs[NDIRECT] = self.balloc()?;
+                }
+                let indirect = din.addrs[NDIRECT];
+                let mut x = disk::get_addr(self.block(indirect), fbn - NDIRECT);
+                if x == 0 {
+                    x = self.balloc()?;
+                    disk::put_addr(self.block_mut(indirect), fbn - NDIRECT, x);
+                }
+                x
+            };
+            let n1 = data.len().min((fbn + 1) * BSIZE - off);
+            let start = off - fbn * BSIZE;
+            self.block_mut(x)[start..start + n1].copy_from_slice(&data[..n1]);
+            off += n1;
+            data = &data[n1..];
+        }
+        din.size = off as c_uint;
+        self.winode(inum, &din);
+        Ok(())
+    }
+
+    /// Returns the block number of block `fbn` of the file `din`, or 0 if it has none.
+    pub fn bmap(&self, din: &DInode, fbn: usize) -> u32 {
+        if fbn < NDIRECT {
+            din.addrs[fbn]
+        } else {
+            match self.get_block(din.addrs[NDIRECT]) {
+                Some(indirect) if din.addrs[NDIRECT] != 0 && fbn < MAXFILE => {
+                    disk::get_addr(indirect, fbn - NDIRECT)
+                }
+                _ => 0,
+            }
+        }
+    }
+
+    /// Returns the contents of the file `din`. Blocks it doesn't have, like those past the end of
+    /// the root directory, read as zeros, and so do block numbers outside the image. At most
+    /// `MAXFILE` blocks are read.
+    pub fn read_inode(&self, din: &DInode) -> Vec<u8> {
+        let size = (din.size as usize).min(MAXFILE * BSIZE);
+        let mut data = Vec::with_capacity(size);
+        let mut fbn = 0;
+        while data.len() < size {
+            let n = BSIZE.min(size - data.len());
+            let b = self.bmap(din, fbn);
+            match self.get_block(b) {
+                Some(block) if b != 0 => data.extend_from_slice(&block[..n]),
+                _ => data.resize(data.len() + n, 0),
+            }
+            fbn += 1;
+        }
+        data
+    }
+
+    /// Returns the contents of inode `inum`.
+    pub fn read(&self, inum: u32) -> Vec<u8> {
+        self.read_inode(&self.rinode(inum))
+    }
+
+    /// Returns the entries of directory `dir`, including free slots.
+    pub fn dirents(&self, dir: u32) -> Vec<Dirent> {
+        self.read(dir)
+            .chunks_exact(Dirent::SIZE)
+            .map(Dirent::read)
+            .collect()
+    }
+
+    fn append_dirent(&mut self, dir: u32, name: &str, inum: u32) -> Result<()> {
+        let mut de = Dirent {
+            inum: inum as c_ushort,
+            ..Dirent::default()
+        };
+        de.name[..name.len()].copy_from_slice(name.as_bytes());
+        let mut buf = [0; Dirent::SIZE];
+        de.write(&mut buf);
+        self.iappend(dir, &buf)
+    }
+
+    /// Returns the inode number of the entry `name` in directory `dir`.
+    pub fn lookup(&self, dir: u32, name: &str) -> Option<u32> {
+        self.dirents(dir)
+            .iter()
+            .find(|de| de.inum != 0 && dirent_name(de) == name.as_bytes())
+            .map(|de| de.inum as u32)
+    }
+
+    /// Returns the inode number of the file at `path`, which is relative to the root directory.
+    pub fn namei(&self, path: &str) -> Option<u32> {
+        let mut inum = ROOTINO;
+        for name in path.split('/').filter(|name| !name.is_empty()) {
+            if self.inode(inum)?.typ != T_DIR {
+                return None;
+            }
+            inum = self.lookup(inum, name)?;
+            self.inode(inum)?;
+        }
+        Some(inum)
+    }
+
+    /// Adds an entry `name` for inode `inum` to directory `dir`.
+    pub fn link(&mut self, dir: u32, name: &str, inum: u32) -> Result<()> {
+        check_name(name)?;
+        if self.lookup(dir, name).is_some() {
+            return Err(Error::AlreadyExists(name.to_string()));
+        }
+        self.append_dirent(dir, name, inum)
+    }
+
+    /// Creates a file `name` in directory `dir` holding `data`. Returns its inode number.
+    pub fn create_file(&mut self, dir: u32, name: &str, data: &[u8]) -> Result<u32> {
+        check_name(name)?;
+        let inum = self.ialloc(T_FILE)?;
+        self.link(dir, name, inum)?;
+        self.iappend(inum, data)?;
+        Ok(inum)
+    }
+
+    /// Creates an empty directory `name` in directory `dir`. Returns its inode number.
+    pub fn mkdir(&mut self, dir: u32, name: &str) -> Result<u32> {
+        check_name(name)?;
+        let inum = self.ialloc(T_DIR)?;
+        self.append_dirent(inum, ".", inum)?;
+        self.append_dirent(inum, "..", dir)?;
+        self.link(dir, name, inum)?;
+        // The new directory's ".." links to the parent, as when the kernel creates a directory.
+        let mut parent = self.rinode(dir);
+        parent.nlink += 1;
+        self.winode(dir, &parent);
+        Ok(inum)
+    }
+
+    /// Returns the inode number of the directory at `path`, creating it and any missing parents.
+    pub fn mkdir_all(&mut self, path: &str) -> Result<u32> {
+        let mut dir = ROOTINO;
+        for name in path.split('/').filter(|name| !name.is_empty()) {
+            dir = match self.lookup(dir, name) {
+                Some(inum) if self.rinode(inum).typ == T_DIR => inum,
+                Some(_) => return Err(Error::NotADirectory(name.to_string())),
+                None => self.mkdir(dir, name)?,
+            };
+        }
+        Ok(dir)
+    }
+
+    /// Rounds the root directory's size up and writes the bitmap. Returns the finished image.
+    pub fn finish(mut self) -> Vec<u8> {
+        // mkfs.c does this so the root directory ends on a block boundary; keep it so images
+        // stay identical.
+        let mut root = self.rinode(ROOTINO);
+        root.size = ((root.size / BSIZE as u32) + 1) * BSIZE as u32;
+        self.winode(ROOTINO, &root);
+
+        for b in 0..self.freeblock {
+            let bit = b as usize % BPB;
+            self.block_mut(self.sb.bblock(b))[bit / 8] |= 1 << (bit % 8);
+        }
+        self.blocks
+    }
+}
+
+/// Returns the name in `de` without the null padding.
+pub fn dirent_name(de: &Dirent) -> &[u8] {
+    let len = de.name.iter().position(|&c| c == 0).unwrap_or(DIRSIZ);
+    &de.name[..len]
+}
+
+fn check_name(name: &str) -> Result<()> {
+    if name.is_empty()
+        || name.len() > DIRSIZ
+        || name == "."
+        || name == ".."
+        || name.contains(['/', '\0'])
+    {
+        return Err(Error::BadName(name.to_string()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(image: &Image, dir: u32) -> Vec<String> {
+        image
+            .dirents(dir)
+            .iter()
+            .filter(|de| de.inum != 0)
+            .map(|de| String::from_utf8(dirent_name(de).to_vec()).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn layout() {
+        let image = Image::new(2000, 200).unwrap();
+        let sb = image.superblock();
+        assert_eq!(image.nmeta(), 46);
+        assert_eq!((sb.logstart, sb.inodestart, sb.bmapstart), (2, 32, 45));
+        assert_eq!(sb.nblocks, 2000 - 46);
+        assert_eq!(image.used_blocks(), 47);
+        assert!(matches!(
+            Image::new(40, 200).err(),
+            Some(Error::TooSmall { size: 40, .. })
+        ));
+    }
+
+    #[test]
+    fn files_and_directories() {
+        let mut image = Image::new(2000, 200).unwrap();
+        let data: Vec<u8> = (0..(NDIRECT + 3) * BSIZE).map(|i| i as u8).collect();
+        let file = image.create_file(ROOTINO, "big", &data).unwrap();
+        assert_eq!(image.read(file), data);
+        let bin = image.mkdir_all("/usr/bin").unwrap();
+        assert_eq!(image.mkdir_all("usr//bin/"), Ok(bin));
+        image.create_file(bin, "ls", b"ls").unwrap();
+
+        assert_eq!(names(&image, ROOTINO), [".", "..", "big", "usr"]);
+        let usr = image.lookup(ROOTINO, "usr").unwrap();
+        assert_eq!(names(&image, usr), [".", "..", "bin"]);
+        assert_eq!(image.lookup(usr, ".."), Some(ROOTINO));
+        assert_eq!(image.rinode(ROOTINO).nlink, 2);
+        assert_eq!(image.rinode(usr).nlink, 2);
+        assert_eq!(image.rinode(bin).nlink, 1);
+        assert_eq!(image.read(image.lookup(bin, "ls").unwrap()), b"ls");
+    }
+
+    #[test]
+    fn errors() {
+        let mut image = Image::new(60, 20).unwrap();
+        image.create_file(ROOTINO, "a", b"").unwrap();
+        assert_eq!(
+            image.create_file(ROOTINO, "a", b""),
+            Err(Error::AlreadyExists("a".to_string()))
+        );
+        assert_eq!(
+            image.mkdir_all("/a/b"),
+            Err(Error::NotADirectory("a".to_string()))
+        );
+        assert!(matches!(
+            image.create_file(ROOTINO, "fifteen-letters", b""),
+            Err(Error::BadName(_))
+        ));
+        assert_eq!(
+            image.create_file(ROOTINO, "big", &[0; 30 * BSIZE]),
+            Err(Error::OutOfBlocks)
+        );
+        while image.freeinode < 20 {
+            image.ialloc(T_FILE).unwrap();
+        }
+        assert_eq!(image.ialloc(T_FILE), Err(Error::OutOfInodes));
+    }
+}
@@ -0,0 +1,6 @@
+//! Host tools for xv6 file system images: `mkfs`, which builds `fs.img`, and `fsimg`, which looks
+//! inside an image and checks it for consistency.
+
+pub mod disk;
+pub mod fsck;
+pub mod image;
@@ -0,0 +1,167 @@
+//! Rust version of `mkfs/mkfs.c`. Builds an xv6 file system image from files on the build machine.
+//!
+//! With only an image name and files, the image is identical to the one `mkfs.c` builds: each file
+//! is put in the root directory under its base name, with a leading `_` removed. (The binaries are
+//! named `_rm`, `_cat`, etc. to keep the build operating system from trying to execute them in
+//! place of system binaries like rm and cat.) The options add to that:
+//!
+//! - `-s BLOCKS` and `-i INODES` set the size of the image and the number of inodes.
+//! - `-d /PATH` creates an empty directory, along with any missing parents.
+//! - A host directory is copied into the root directory with everything in it.
+//! - `/PATH=FILE` puts the host file or directory `FILE` at `/PATH` in the image instead of the
+//!   root directory.
+
+use mkfs::image::Image;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process;
+use xv6_defs::fs::{BPB, ROOTINO};
+use xv6_defs::param::FSSIZE;
+
+/// Default number of inodes, the same as mkfs.c.
+const NINODES: u32 = 200;
+
+const USAGE: &str = "Usage: mkfs [-s blocks] [-i inodes] [-d /dir]... fs.img [[/path=]file]...";
+
+/// Prints `msg` and exits with an error.
+fn fail(msg: &str) -> ! {
+    eprintln!("mkfs: {}", msg);
+    process::exit(1)
+}
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(1)
+}
+
+/// Returns the number given as the value of option `opt`.
+fn parse_num(opt: &str, value: Option<String>) -> u32 {
+    let value = value.unwrap_or_else(|| usage());
+    value
+        .parse()
+        .unwrap_or_else(|_| fail(&format!("{}: invalid number '{}'", opt, value)))
+}
+
+/// Returns the final component of `path` as a string.
+fn base_name(path: &Path) -> String {
+    match path.file_name().and_then(|name| name.to_str()) {
+        Some(name) => name.to_string(),
+        None => fail(&format!("{}: no usable file name", path.display())),
+    }
+}
+
+/// Adds the host file or directory `src` to directory `dir` of the image as `name`.
+fn add(image: &mut Image, dir: u32, name: &str, src: &Path) {
+    let meta = fs::metadata(src).unwrap_or_else(|e| fail(&format!("{}: {}", src.display(), e)));
+    let result = if meta.is_dir() {
+        image
+            .mkdir(dir, name)
+            .map(|inum| add_tree(image, inum, src))
+    } else {
+        let data = fs::read(src).unwrap_or_else(|e| fail(&format!("{}: {}", src.display(), e)));
+        image.create_file(dir, name, &data).map(|_| ())
+    };
+    if let Err(e) = result {
+        fail(&format!("{}: {}", src.display(), e));
+    }
+}
+
+/// Copies everything in the host directory `src` into directory `dir` of the image, in order of
+/// name so the image doesn't depend on the order the host lists directories in.
+fn add_tree(image: &mut Image, dir: u32, src: &Path) {
+    let entries = fs::read_dir(src).unwrap_or_else(|e| fail(&format!("{}: {}", src.display(), e)));
+    let mut paths = entries
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap_or_else(|e| fail(&format!("{}: {}", src.display(), e)));
+    paths.sort();
+    for path in paths {
+        let meta =
+            fs::metadata(&path).unwrap_or_else(|e| fail(&format!("{}: {}", path.display(), e)));
+        if !meta.is_file() && !meta.is_dir() {
+            eprintln!("mkfs: skipping {}: not a file or directory", path.display());
+            continue;
+        }
+        add(image, dir, &base_name(&path), &path);
+    }
+}
+
+/// Adds one file argument to the image.
+fn add_arg(image: &mut Image, arg: &str) {
+    if arg.starts_with('/') {
+        if let Some((dest, src)) = arg.split_once('=') {
+            let dest = dest.trim_end_matches('/');
+            match dest.rsplit_once('/') {
+                Some((parent, name)) => {
+                    let dir = image
+                        .mkdir_all(parent)
+                        .unwrap_or_else(|e| fail(&format!("{}: {}", dest, e)));
+                    add(image, dir, name, Path::new(src));
+                }
+                // "/=dir" copies the contents of dir into the root directory.
+                None => add_tree(image, ROOTINO, Path::new(src)),
+            }
+            return;
+        }
+    }
+
+    let src = Path::new(arg);
+    let mut name = base_name(src);
+    if src.is_file() && name.starts_with('_') {
+        name.remove(0);
+    }
+    add(image, ROOTINO, &name, src);
+}
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let mut size = FSSIZE as u32;
+    let mut ninodes = NINODES;
+    let mut dirs = Vec::new();
+    let img = loop {
+        let arg = args.next().unwrap_or_else(|| usage());
+        match arg.as_str() {
+            "-s" | "--size" => size = parse_num(&arg, args.next()),
+            "-i" | "--inodes" => ninodes = parse_num(&arg, args.next()),
+            "-d" | "--mkdir" => dirs.push(args.next().unwrap_or_else(|| usage())),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            "--" => break args.next().unwrap_or_else(|| usage()),
+            _ if arg.starts_with('-') => usage(),
+            _ => break arg,
+        }
+    };
+
+    let mut image = Image::new(size, ninodes).unwrap_or_else(|e| fail(&e.to_string()));
+    let sb = *image.superblock();
+    println!(
+        "nmeta {} (boot, super, log blocks {} inode blocks {}, bitmap blocks {}) blocks {} total {}",
+        image.nmeta(),
+        sb.nlog,
+        sb.bmapstart - sb.inodestart,
+        image.nmeta() - sb.bmapstart,
+        sb.nblocks,
+        sb.size
+    );
+
+    for dir in &dirs {
+        if let Err(e) = image.mkdir_all(dir) {
+            fail(&format!("{}: {}", dir, e));
+        }
+    }
+    for arg in args {
+        add_arg(&mut image, &arg);
+    }
+
+    let used = image.used_blocks();
+    println!("balloc: first {} blocks have been allocated", used);
+    for b in 0..(used - 1) / BPB as u32 + 1 {
+        println!("balloc: write bitmap block at sector {}", sb.bmapstart + b);
+    }
+    if let Err(e) = fs::write(&img, image.finish()) {
+        fail(&format!("{}: {}", img, e));
+    }
+}
@@ -0,0 +1,35 @@
+//! Generates the system call numbers in `syscall.rs` from `kernel/syscall.h`, so the C and Rust
+//! sides of the syscall ABI share a single table.
+
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+fn main() {
+    let manifest_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let header = Path::new(&manifest_dir).join("../../kernel/syscall.h");
+    println!("cargo:rerun-if-changed={}", header.display());
+
+    let text = fs::read_to_string(&header)
+        .unwrap_or_else(|e| panic!("couldn't read {}: {}", header.display(), e));
+
+    let mut out = String::new();
+    for line in text.lines() {
+        let mut words = line.split_whitespace();
+        if words.next() != Some("#define") {
+            continue;
+        }
+        let (name, num) = match (words.next(), words.next()) {
+            (Some(name), Some(num)) if name.starts_with("SYS_") => (name, num),
+            _ => continue,
+        };
+        let num: usize = num
+            .parse()
+            .unwrap_or_else(|_| panic!("bad system call number for {}: {}", name, num));
+        writeln!(out, "pub const {}: usize = {};", name, num).unwrap();
+    }
+
+    let dest = Path::new(&env::var("OUT_DIR").unwrap()).join("syscall.rs");
+    fs::write(dest, out).unwrap();
+}
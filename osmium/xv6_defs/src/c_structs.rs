@@ -9,8 +9,8 @@ pub type PagetableT = *mut u64;
 // Saved registers for kernel context switches.
 #[repr(C)]
 pub struct SpinLock {
-    locked: c_uint,
-    name: *const c_char,
+    pub locked: c_uint,
+    pub name: *const c_char,
     // Placeholder pointer type.
     cpu: *mut c_void,
     n: c_uint,
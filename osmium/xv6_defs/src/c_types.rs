@@ -5,6 +5,7 @@
 
 // Rust types for builtin C types.
 pub type c_int = i32;
+pub type c_short = i16;
 // The C standard allows the char type to be signed or unsigned. Most compilers use signed chars,
 // but defining the c_int type as u8 shouldn't cause issues because this behavior isn't relied
 // upon. The char type is almost always used as storage for ASCII, which only goes up to 127, or
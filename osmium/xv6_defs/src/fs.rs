@@ -0,0 +1,121 @@
+//! On-disk file system definitions from kernel/fs.h
+//!
+//! Disk layout:
+//! `[ boot block | super block | log | inode blocks | free bit map | data blocks ]`
+//!
+//! All multi-byte fields are little endian on disk.
+
+use crate::c_types::*;
+use crate::param::LOGSIZE;
+use core::mem::{offset_of, size_of};
+
+/// Root i-number.
+pub const ROOTINO: c_uint = 1;
+/// Block size.
+pub const BSIZE: usize = 1024;
+
+/// The super block describes the disk layout. mkfs computes it and builds an initial file system.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SuperBlock {
+    /// Must be `FSMAGIC`.
+    pub magic: c_uint,
+    /// Size of file system image (blocks).
+    pub size: c_uint,
+    /// Number of data blocks.
+    pub nblocks: c_uint,
+    /// Number of inodes.
+    pub ninodes: c_uint,
+    /// Number of log blocks.
+    pub nlog: c_uint,
+    /// Block number of first log block.
+    pub logstart: c_uint,
+    /// Block number of first inode block.
+    pub inodestart: c_uint,
+    /// Block number of first free map block.
+    pub bmapstart: c_uint,
+}
+
+impl SuperBlock {
+    /// Block containing inode `i` (`IBLOCK` in C).
+    pub const fn iblock(&self, i: c_uint) -> c_uint {
+        i / IPB as c_uint + self.inodestart
+    }
+
+    /// Block of free map containing bit for block `b` (`BBLOCK` in C).
+    pub const fn bblock(&self, b: c_uint) -> c_uint {
+        b / BPB as c_uint + self.bmapstart
+    }
+}
+
+pub const FSMAGIC: c_uint = 0x10203040;
+
+pub const NDIRECT: usize = 12;
+pub const NINDIRECT: usize = BSIZE / size_of::<c_uint>();
+pub const MAXFILE: usize = NDIRECT + NINDIRECT;
+
+/// On-disk inode structure.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DInode {
+    /// File type, one of the `T_*` constants in `stat`, or 0 if the inode is free.
+    pub typ: c_short,
+    /// Major device number (`T_DEVICE` only).
+    pub major: c_short,
+    /// Minor device number (`T_DEVICE` only).
+    pub minor: c_short,
+    /// Number of links to inode in file system.
+    pub nlink: c_short,
+    /// Size of file (bytes).
+    pub size: c_uint,
+    /// Data block addresses. The last one is the block of indirect addresses.
+    pub addrs: [c_uint; NDIRECT + 1],
+}
+
+/// Inodes per block.
+pub const IPB: usize = BSIZE / size_of::<DInode>();
+
+/// Bitmap bits per block.
+pub const BPB: usize = BSIZE * 8;
+
+/// Maximum length of a directory entry name. Names of exactly this length aren't null terminated.
+pub const DIRSIZ: usize = 14;
+
+/// A directory is a file containing a sequence of these entries. Slots with `inum` 0 are free.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Dirent {
+    pub inum: c_ushort,
+    pub name: [c_char; DIRSIZ],
+}
+
+/// Contents of the log header block, the first block of the log, from kernel/log.c. A nonzero `n`
+/// means a transaction was committed but not yet installed, and is replayed on the next boot.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LogHeader {
+    /// Number of logged blocks.
+    pub n: c_int,
+    /// Home block numbers of the logged blocks, which follow the header in the log.
+    pub block: [c_int; LOGSIZE],
+}
+
+// Check the layouts against the C structs, which are shared with the C kernel and stored on disk.
+const _: () = {
+    assert!(size_of::<SuperBlock>() == 32);
+    assert!(offset_of!(SuperBlock, nblocks) == 8);
+    assert!(offset_of!(SuperBlock, bmapstart) == 28);
+
+    assert!(size_of::<DInode>() == 64);
+    assert!(offset_of!(DInode, nlink) == 6);
+    assert!(offset_of!(DInode, size) == 8);
+    assert!(offset_of!(DInode, addrs) == 12);
+    assert!(BSIZE.is_multiple_of(size_of::<DInode>()));
+
+    assert!(size_of::<Dirent>() == 16);
+    assert!(offset_of!(Dirent, name) == 2);
+    assert!(BSIZE.is_multiple_of(size_of::<Dirent>()));
+
+    assert!(offset_of!(LogHeader, block) == 4);
+    assert!(size_of::<LogHeader>() <= BSIZE);
+};
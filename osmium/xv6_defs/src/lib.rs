@@ -5,4 +5,9 @@
 
 pub mod c_structs;
 pub mod c_types;
+pub mod errno;
+pub mod fcntl;
+pub mod fs;
 pub mod param;
+pub mod stat;
+pub mod syscall;
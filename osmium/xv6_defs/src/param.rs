@@ -1,3 +1,13 @@
 //! Parameters defined in kernel/param.h
 
+/// Maximum number of CPUs.
+pub const NCPU: usize = 8;
 pub const NOFILE: usize = 16;
+pub const MAXARG: usize = 32;
+pub const MAXPATH: usize = 128;
+/// Max # of blocks any FS op writes.
+pub const MAXOPBLOCKS: usize = 10;
+/// Max data blocks in on-disk log.
+pub const LOGSIZE: usize = MAXOPBLOCKS * 3;
+/// Size of file system in blocks.
+pub const FSSIZE: usize = 2000;
@@ -0,0 +1,87 @@
+//! File metadata from kernel/stat.h
+
+use crate::c_types::*;
+use core::mem::{offset_of, size_of};
+
+/// Directory.
+pub const T_DIR: c_short = 1;
+/// File.
+pub const T_FILE: c_short = 2;
+/// Device.
+pub const T_DEVICE: c_short = 3;
+
+/// Type of the inode a file refers to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileType {
+    Dir,
+    File,
+    Device,
+    /// A type value the kernel doesn't define. Shouldn't happen unless the file system is corrupt.
+    Unknown(c_short),
+}
+
+impl FileType {
+    /// Returns the type for a `T_*` value.
+    pub fn from_raw(t: c_short) -> FileType {
+        match t {
+            T_DIR => FileType::Dir,
+            T_FILE => FileType::File,
+            T_DEVICE => FileType::Device,
+            t => FileType::Unknown(t),
+        }
+    }
+
+    /// Returns the `T_*` value of the type, as stored in `struct stat`.
+    pub fn to_raw(self) -> c_short {
+        match self {
+            FileType::Dir => T_DIR,
+            FileType::File => T_FILE,
+            FileType::Device => T_DEVICE,
+            FileType::Unknown(t) => t,
+        }
+    }
+}
+
+/// `struct stat`, filled in by the `fstat` system call.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Stat {
+    /// File system's disk device.
+    pub dev: c_int,
+    /// Inode number.
+    pub ino: c_uint,
+    /// Type of file, one of the `T_*` constants.
+    pub typ: c_short,
+    /// Number of links to the file.
+    pub nlink: c_short,
+    /// Size of the file in bytes.
+    pub size: u64,
+}
+
+impl Stat {
+    /// Returns the type of the file.
+    pub fn file_type(&self) -> FileType {
+        FileType::from_raw(self.typ)
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.typ == T_DIR
+    }
+
+    pub fn is_file(&self) -> bool {
+        self.typ == T_FILE
+    }
+
+    pub fn is_device(&self) -> bool {
+        self.typ == T_DEVICE
+    }
+}
+
+// The kernel copies its `struct stat` straight into a `Stat`, so the layouts must match.
+const _: () = {
+    assert!(size_of::<Stat>() == 24);
+    assert!(offset_of!(Stat, ino) == 4);
+    assert!(offset_of!(Stat, typ) == 8);
+    assert!(offset_of!(Stat, nlink) == 10);
+    assert!(offset_of!(Stat, size) == 16);
+};
@@ -0,0 +1,8 @@
+//! System call numbers defined in kernel/syscall.h
+//!
+//! The constants are generated by `build.rs` from the C header, which is the single source of
+//! truth for the syscall ABI.
+
+#![allow(non_upper_case_globals)]
+
+include!(concat!(env!("OUT_DIR"), "/syscall.rs"));
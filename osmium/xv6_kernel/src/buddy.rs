@@ -0,0 +1,516 @@
+//! Rust version of the buddy allocator that was kernel/buddy.c.
+//!
+//! Buddy allocator for kernel memory smaller than a page, such as file structs and pipes. It
+//! manages the memory given to `bd_init()` as a heap whose size is a power of two, made of blocks
+//! of size `k` that are `LEAF_SIZE << k` bytes. Allocating splits a larger free block in halves
+//! until it fits the request, and freeing merges a block with its buddy (the other half of the
+//! block it was split from) whenever the buddy is free too.
+//!
+//! For each size the allocator keeps a free list, a bitmap of which blocks are allocated and,
+//! except for the smallest size, a bitmap of which blocks have been split. These are stored at the
+//! start of the heap, which is marked allocated along with the part of the heap past the end of
+//! the memory.
+
+use crate::c_defs::*;
+use crate::list::{Link, List, Node};
+use core::ffi::c_void;
+use core::mem::{self, MaybeUninit};
+use core::ptr;
+use xv6_defs::c_structs::*;
+
+/// The smallest block size.
+const LEAF_SIZE: usize = 16;
+
+/// Size of a block of size `k`.
+const fn blk_size(k: usize) -> usize {
+    LEAF_SIZE << k
+}
+
+/// Rounds `n` up to the next multiple of `sz`.
+const fn roundup(n: usize, sz: usize) -> usize {
+    n.div_ceil(sz) * sz
+}
+
+/// Returns the first `k` such that a size `k` block holds `n` bytes.
+fn firstk(n: usize) -> usize {
+    let mut k = 0;
+    while blk_size(k) < n {
+        k += 1;
+    }
+    k
+}
+
+fn log2(mut n: usize) -> usize {
+    let mut k = 0;
+    while n > 1 {
+        k += 1;
+        n >>= 1;
+    }
+    k
+}
+
+/// A free block, which holds its free list link.
+#[repr(C)]
+struct Block {
+    link: Link,
+}
+
+unsafe impl Node for Block {}
+
+/// One bit per block of some size.
+#[derive(Clone, Copy)]
+struct Bitmap(*mut u8);
+
+impl Bitmap {
+    unsafe fn is_set(self, i: usize) -> bool {
+        *self.0.add(i / 8) & (1 << (i % 8)) != 0
+    }
+
+    unsafe fn set(self, i: usize) {
+        *self.0.add(i / 8) |= 1 << (i % 8);
+    }
+
+    unsafe fn clear(self, i: usize) {
+        *self.0.add(i / 8) &= !(1 << (i % 8));
+    }
+
+    /// Prints the bitmap of `len` bits as a list of ranges of 1 bits.
+    unsafe fn print(self, len: usize) {
+        let mut start = None;
+        for b in 0..=len {
+            match (start, b < len && self.is_set(b)) {
+                (None, true) => start = Some(b),
+                (Some(lb), false) => {
+                    print!(" [{}, {})", lb, b);
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+        println!();
+    }
+}
+
+/// The allocator's state for one block size.
+#[repr(C)]
+struct SzInfo {
+    free: List<Block>,
+    alloc: Bitmap,
+    /// Not used for size 0, since the smallest blocks are never split.
+    split: Bitmap,
+}
+
+struct Buddy {
+    lock: SpinLock,
+    /// Start of the heap.
+    base: *mut u8,
+    /// Number of block sizes. The one block of the largest size is the whole heap.
+    nsizes: usize,
+    /// `nsizes` entries, at the start of the heap.
+    sizes: *mut SzInfo,
+}
+
+impl Buddy {
+    fn new() -> Buddy {
+        Buddy {
+            lock: unsafe { mem::zeroed() },
+            base: ptr::null_mut(),
+            nsizes: 0,
+            sizes: ptr::null_mut(),
+        }
+    }
+
+    /// Largest index in `sizes`.
+    fn max_size(&self) -> usize {
+        self.nsizes - 1
+    }
+
+    fn heap_size(&self) -> usize {
+        blk_size(self.max_size())
+    }
+
+    /// Number of blocks of size `k`.
+    fn nblk(&self, k: usize) -> usize {
+        1 << (self.max_size() - k)
+    }
+
+    unsafe fn info(&self, k: usize) -> &SzInfo {
+        &*self.sizes.add(k)
+    }
+
+    unsafe fn info_mut(&mut self, k: usize) -> &mut SzInfo {
+        &mut *self.sizes.add(k)
+    }
+
+    /// Index of the size `k` block containing address `p`.
+    fn blk_index(&self, k: usize, p: *const u8) -> usize {
+        (p as usize - self.base as usize) / blk_size(k)
+    }
+
+    /// Index of the first size `k` block that starts at or after `p`.
+    fn blk_index_next(&self, k: usize, p: *const u8) -> usize {
+        (p as usize - self.base as usize).div_ceil(blk_size(k))
+    }
+
+    /// Address of size `k` block `bi`.
+    fn addr(&self, k: usize, bi: usize) -> *mut u8 {
+        self.base.wrapping_add(bi * blk_size(k))
+    }
+
+    /// Sets up the allocator to manage the memory `[base, stop)`.
+    unsafe fn init(&mut self, base: *mut u8, stop: *mut u8) {
+        let mut p = roundup(base as usize, LEAF_SIZE) as *mut u8;
+        self.base = p;
+        let len = stop as usize - p as usize;
+
+        // Compute the number of sizes we need to manage [base, stop), rounding the heap up to the
+        // next power of 2.
+        self.nsizes = log2(len / LEAF_SIZE) + 1;
+        if len > self.heap_size() {
+            self.nsizes += 1;
+        }
+        println!(
+            "bd: memory sz is {} bytes; allocate an size array of length {}",
+            len, self.nsizes
+        );
+
+        self.sizes = p as *mut SzInfo;
+        ptr::write_bytes(self.sizes, 0, self.nsizes);
+        p = p.add(mem::size_of::<SzInfo>() * self.nsizes);
+
+        for k in 0..self.nsizes {
+            let sz = roundup(self.nblk(k), 8) / 8;
+            let info = self.info_mut(k);
+            info.free.init();
+            info.alloc = Bitmap(p);
+            ptr::write_bytes(p, 0, sz);
+            p = p.add(sz);
+        }
+        for k in 1..self.nsizes {
+            let sz = roundup(self.nblk(k), 8) / 8;
+            self.info_mut(k).split = Bitmap(p);
+            ptr::write_bytes(p, 0, sz);
+            p = p.add(sz);
+        }
+        p = roundup(p as usize, LEAF_SIZE) as *mut u8;
+
+        // Keep the allocator from handing out its own data structures.
+        let meta = p as usize - self.base as usize;
+        println!(
+            "bd: {} meta bytes for managing {} bytes of memory",
+            meta,
+            self.heap_size()
+        );
+        self.mark(self.base, p);
+
+        // Or the part of the heap past the end of the memory.
+        let unavailable = roundup(self.heap_size() - len, LEAF_SIZE);
+        println!("bd: {:#x} bytes unavailable", unavailable);
+        let bd_end = self.addr(0, (self.heap_size() - unavailable) / LEAF_SIZE);
+        self.mark(bd_end, self.addr(self.max_size(), 1));
+
+        let free = self.initfree(p, bd_end);
+        if free != self.heap_size() - meta - unavailable {
+            println!("free {} {}", free, self.heap_size() - meta - unavailable);
+            panic("bd_init: free mem\0".as_ptr());
+        }
+    }
+
+    /// Marks `[start, stop)` allocated at every size.
+    unsafe fn mark(&mut self, start: *mut u8, stop: *mut u8) {
+        if !(start as usize).is_multiple_of(LEAF_SIZE) || !(stop as usize).is_multiple_of(LEAF_SIZE)
+        {
+            panic("bd_mark\0".as_ptr());
+        }
+
+        for k in 0..self.nsizes {
+            let info = self.info(k);
+            for bi in self.blk_index(k, start)..self.blk_index_next(k, stop) {
+                // A block that is allocated at size k is split too.
+                if k > 0 {
+                    info.split.set(bi);
+                }
+                info.alloc.set(bi);
+            }
+        }
+    }
+
+    /// If exactly one of block `bi` and its buddy is allocated, puts the other on the free list
+    /// for size `k`. Returns the number of bytes freed.
+    unsafe fn initfree_pair(&mut self, k: usize, bi: usize) -> usize {
+        let buddy = bi ^ 1;
+        let alloc = self.info(k).alloc;
+        if alloc.is_set(bi) == alloc.is_set(buddy) {
+            return 0;
+        }
+        let free = if alloc.is_set(bi) { buddy } else { bi };
+        let block = self.addr(k, free) as *mut Block;
+        self.info_mut(k).free.push(block);
+        blk_size(k)
+    }
+
+    /// Initializes the free lists for the memory between the allocator's data structures, which end
+    /// at `left`, and the unavailable memory, which starts at `right`. At each size only the pairs
+    /// of blocks at the two edges can have one block allocated and the other free. Returns the
+    /// number of bytes freed.
+    unsafe fn initfree(&mut self, left: *mut u8, right: *mut u8) -> usize {
+        let mut free = 0;
+        for k in 0..self.max_size() {
+            let left = self.blk_index_next(k, left);
+            let right = self.blk_index(k, right);
+            free += self.initfree_pair(k, left);
+            // With no unavailable memory, right is past the last block.
+            if right > left && right < self.nblk(k) {
+                free += self.initfree_pair(k, right);
+            }
+        }
+        free
+    }
+
+    /// Allocates a block of at least `nbytes` bytes. Returns null if there is no free block large
+    /// enough.
+    unsafe fn malloc(&mut self, nbytes: usize) -> *mut u8 {
+        acquire(&mut self.lock);
+
+        // Find a free block >= nbytes, starting with the smallest size possible.
+        let fk = firstk(nbytes);
+        let k = match (fk..self.nsizes).find(|&k| !self.info(k).free.is_empty()) {
+            Some(k) => k,
+            None => {
+                release(&mut self.lock);
+                return ptr::null_mut();
+            }
+        };
+
+        // Pop it and split it down to size fk, putting the second half of each split on the free
+        // list for the next size down.
+        let p = self.info_mut(k).free.pop().unwrap() as *mut u8;
+        self.info(k).alloc.set(self.blk_index(k, p));
+        for k in (fk + 1..=k).rev() {
+            let q = p.add(blk_size(k - 1));
+            self.info(k).split.set(self.blk_index(k, p));
+            self.info(k - 1).alloc.set(self.blk_index(k - 1, p));
+            self.info_mut(k - 1).free.push(q as *mut Block);
+        }
+
+        release(&mut self.lock);
+        p
+    }
+
+    /// Returns the size of the allocated block at `p`, which is the size below the largest one
+    /// that is split.
+    unsafe fn size(&self, p: *mut u8) -> usize {
+        (0..self.max_size())
+            .find(|&k| self.info(k + 1).split.is_set(self.blk_index(k + 1, p)))
+            .unwrap_or(self.max_size())
+    }
+
+    /// Frees the block at `p`, which was returned by `malloc`.
+    unsafe fn free(&mut self, mut p: *mut u8) {
+        acquire(&mut self.lock);
+
+        let mut k = self.size(p);
+        while k < self.max_size() {
+            let bi = self.blk_index(k, p);
+            let buddy = bi ^ 1;
+            let alloc = self.info(k).alloc;
+            alloc.clear(bi);
+            if alloc.is_set(buddy) {
+                break;
+            }
+            // The buddy is free, so merge with it.
+            let q = self.addr(k, buddy);
+            List::remove(q as *mut Block);
+            if buddy.is_multiple_of(2) {
+                p = q;
+            }
+            // The merged pair isn't split at size k + 1 anymore.
+            self.info(k + 1).split.clear(self.blk_index(k + 1, p));
+            k += 1;
+        }
+        self.info_mut(k).free.push(p as *mut Block);
+
+        release(&mut self.lock);
+    }
+
+    /// Prints the free blocks and the allocated and split bitmaps for each size.
+    unsafe fn print(&mut self) {
+        acquire(&mut self.lock);
+        for k in 0..self.nsizes {
+            let info = self.info(k);
+            print!(
+                "size {} (blksz {} nblk {}): {} free:",
+                k,
+                blk_size(k),
+                self.nblk(k),
+                info.free.iter().count()
+            );
+            for b in info.free.iter() {
+                print!(" {:p}", b);
+            }
+            println!();
+            print!("  alloc:");
+            info.alloc.print(self.nblk(k));
+            if k > 0 {
+                print!("  split:");
+                info.split.print(self.nblk(k));
+            }
+        }
+        release(&mut self.lock);
+    }
+}
+
+static mut BUDDY: MaybeUninit<Buddy> = MaybeUninit::uninit();
+
+unsafe fn buddy() -> &'static mut Buddy {
+    (*ptr::addr_of_mut!(BUDDY)).assume_init_mut()
+}
+
+/// Initialize the buddy allocator: it manages memory from `[base, stop)`.
+#[no_mangle]
+pub unsafe extern "C" fn bd_init(base: *mut c_void, stop: *mut c_void) {
+    let bd = (*ptr::addr_of_mut!(BUDDY)).write(Buddy::new());
+    initlock(&mut bd.lock, "buddy\0".as_ptr());
+    bd.init(base as *mut u8, stop as *mut u8);
+}
+
+/// Allocate `nbytes`, but never a block smaller than 16 bytes. Returns 0 if the memory cannot be
+/// allocated.
+#[no_mangle]
+pub unsafe extern "C" fn bd_malloc(nbytes: u64) -> *mut c_void {
+    buddy().malloc(nbytes as usize) as *mut c_void
+}
+
+/// Free memory pointed to by `p`, which was earlier allocated using `bd_malloc()`.
+#[no_mangle]
+pub unsafe extern "C" fn bd_free(p: *mut c_void) {
+    buddy().free(p as *mut u8);
+}
+
+/// Print the free blocks and the allocated and split bitmaps of each size, for debugging.
+#[no_mangle]
+pub unsafe extern "C" fn bd_print() {
+    buddy().print();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec::Vec;
+
+    #[repr(C, align(16))]
+    #[derive(Clone, Copy)]
+    struct Leaf([u8; LEAF_SIZE]);
+
+    /// Memory of `len` bytes for the allocator to manage.
+    fn memory(len: usize) -> Vec<Leaf> {
+        vec![Leaf([0; LEAF_SIZE]); len / LEAF_SIZE]
+    }
+
+    fn buddy(mem: &mut [Leaf]) -> Buddy {
+        let mut bd = Buddy::new();
+        unsafe {
+            initlock(&mut bd.lock, "buddy\0".as_ptr());
+            let range = mem.as_mut_ptr_range();
+            bd.init(range.start as *mut u8, range.end as *mut u8);
+        }
+        bd
+    }
+
+    /// Returns the number of free blocks of each size.
+    fn free_blocks(bd: &Buddy) -> Vec<usize> {
+        (0..bd.nsizes)
+            .map(|k| unsafe { bd.info(k).free.iter().count() })
+            .collect()
+    }
+
+    fn free_bytes(bd: &Buddy) -> usize {
+        free_blocks(bd)
+            .iter()
+            .enumerate()
+            .map(|(k, n)| n * blk_size(k))
+            .sum()
+    }
+
+    #[test]
+    fn init() {
+        // 40 KiB needs a 64 KiB heap, with the last 24 KiB unavailable.
+        let mut mem = memory(40 * 1024);
+        let bd = buddy(&mut mem);
+        assert_eq!(bd.heap_size(), 64 * 1024);
+        // The size array and the bitmaps take 1956 bytes at the start.
+        assert_eq!(free_bytes(&bd), 40 * 1024 - roundup(1956, LEAF_SIZE));
+        assert_eq!(free_blocks(&bd)[bd.max_size()], 0);
+        assert_eq!(bd.lock.locked, 0);
+    }
+
+    #[test]
+    fn malloc_and_free() {
+        let mut mem = memory(40 * 1024);
+        let mut bd = buddy(&mut mem);
+        let initial = free_blocks(&bd);
+        let nfree = free_bytes(&bd);
+        let range = mem.as_ptr_range();
+
+        let sizes = [1, 16, 17, 100, 1000, 4096, 24];
+        let blocks: Vec<_> = sizes
+            .iter()
+            .map(|&n| (unsafe { bd.malloc(n) }, n))
+            .collect();
+        for (i, &(p, n)) in blocks.iter().enumerate() {
+            let k = firstk(n);
+            assert!(!p.is_null());
+            assert_eq!((p as usize - bd.base as usize) % blk_size(k), 0);
+            assert!(p as *const Leaf >= range.start);
+            assert!(p.wrapping_add(blk_size(k)) as *const Leaf <= range.end);
+            assert_eq!(unsafe { bd.size(p) }, k);
+            for &(q, m) in &blocks[i + 1..] {
+                let disjoint = p as usize + blk_size(k) <= q as usize
+                    || q as usize + blk_size(firstk(m)) <= p as usize;
+                assert!(disjoint);
+            }
+            unsafe { ptr::write_bytes(p, 0xab, n) };
+        }
+        let used: usize = sizes.iter().map(|&n| blk_size(firstk(n))).sum();
+        assert_eq!(free_bytes(&bd), nfree - used);
+
+        for &i in &[3, 0, 6, 5, 1, 4, 2] {
+            unsafe { bd.free(blocks[i].0) };
+        }
+        assert_eq!(free_blocks(&bd), initial);
+        assert_eq!(bd.lock.locked, 0);
+    }
+
+    #[test]
+    fn malloc_until_full() {
+        let mut mem = memory(8 * 1024);
+        let mut bd = buddy(&mut mem);
+        let initial = free_blocks(&bd);
+        let nfree = free_bytes(&bd);
+
+        let mut blocks = Vec::new();
+        loop {
+            let p = unsafe { bd.malloc(LEAF_SIZE) };
+            if p.is_null() {
+                break;
+            }
+            blocks.push(p);
+        }
+        assert_eq!(blocks.len() * LEAF_SIZE, nfree);
+        assert_eq!(free_bytes(&bd), 0);
+
+        for p in blocks {
+            unsafe { bd.free(p) };
+        }
+        assert_eq!(free_blocks(&bd), initial);
+    }
+
+    #[test]
+    fn malloc_too_big() {
+        let mut mem = memory(8 * 1024);
+        let mut bd = buddy(&mut mem);
+        assert!(unsafe { bd.malloc(8 * 1024) }.is_null());
+        assert!(!unsafe { bd.malloc(4 * 1024) }.is_null());
+        assert_eq!(bd.lock.locked, 0);
+    }
+}
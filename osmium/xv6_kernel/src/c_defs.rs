@@ -1,4 +1,7 @@
 //! FFI declarations for functions declared in kernel/defs.h.
+//!
+//! Host unit tests (`cargo test`) don't link against the C kernel, so they get the mock versions
+//! in `mock` of the few functions the tested code calls instead.
 
 #![allow(dead_code)]
 
@@ -6,22 +9,27 @@ use core::ffi::c_void;
 use xv6_defs::c_structs::*;
 use xv6_defs::c_types::*;
 
+#[cfg(not(test))]
 extern "C" {
     pub fn exit(code: c_int);
     pub fn write(x: c_int, y: *const c_void, z: c_int) -> c_int;
     pub fn strlen(s: *const c_char) -> c_int;
     pub fn printf(fmt: *const c_char, args: ...);
 
+    // console.c
+    pub fn consputc(c: c_int);
+
     // kalloc.c
     pub fn kalloc() -> *mut c_void;
     pub fn kfree(pa: *mut c_void);
     pub fn kinit();
 
     // printf.c
-    pub fn panic(s: *const c_char);
+    pub fn panic(s: *const c_char) -> !;
 
     // proc.c
     pub fn growproc(n: c_int) -> c_int;
+    pub fn cpuid() -> c_int;
     pub fn myproc() -> *mut Proc;
     pub fn fork() -> c_int;
     pub fn kill(pid: c_int) -> c_int;
@@ -32,9 +40,12 @@ extern "C" {
     pub fn acquire(lk: *mut SpinLock);
     pub fn release(lk: *mut SpinLock);
     pub fn initlock(lk: *mut SpinLock, name: *const c_char);
+    pub fn push_off();
+    pub fn pop_off();
 
     // string.c
     pub fn memset(dst: *mut c_void, c: c_int, n: c_uint);
+    pub fn memmove(dst: *mut c_void, src: *const c_void, n: c_uint);
 
     // syscall.c
     pub fn argint(n: c_int, ip: *mut c_int) -> c_int;
@@ -43,4 +54,63 @@ extern "C" {
     // trap.c
     pub static mut ticks: c_uint;
     pub static mut tickslock: SpinLock;
+
+    // vm.c
+    pub fn walk(pagetable: PagetableT, va: u64, alloc: c_int) -> *mut u64;
+    pub fn uvmdealloc(pagetable: PagetableT, oldsz: u64, newsz: u64) -> u64;
+
+    // kernel.ld
+    /// First address after the kernel.
+    pub static mut end: c_char;
+}
+
+#[cfg(test)]
+pub use mock::*;
+
+#[cfg(test)]
+mod mock {
+    use super::*;
+    use core::ptr;
+
+    /// Stands in for the end of the kernel image.
+    #[allow(non_upper_case_globals)]
+    pub static mut end: c_char = 0;
+
+    pub unsafe fn consputc(c: c_int) {
+        std::print!("{}", c as u8 as char);
+    }
+
+    pub unsafe fn panic(s: *const c_char) -> ! {
+        let msg = std::ffi::CStr::from_ptr(s as *const _);
+        panic!("{}", msg.to_string_lossy())
+    }
+
+    /// Tests run as CPU 0.
+    pub unsafe fn cpuid() -> c_int {
+        0
+    }
+
+    pub unsafe fn push_off() {}
+
+    pub unsafe fn pop_off() {}
+
+    pub unsafe fn initlock(lk: *mut SpinLock, name: *const c_char) {
+        (*lk).name = name;
+        (*lk).locked = 0;
+    }
+
+    /// Panics if the lock is already held, like `acquire` does when a CPU acquires a lock twice.
+    pub unsafe fn acquire(lk: *mut SpinLock) {
+        assert_eq!((*lk).locked, 0, "acquire");
+        (*lk).locked = 1;
+    }
+
+    pub unsafe fn release(lk: *mut SpinLock) {
+        assert_eq!((*lk).locked, 1, "release");
+        (*lk).locked = 0;
+    }
+
+    pub unsafe fn memset(dst: *mut c_void, c: c_int, n: c_uint) {
+        ptr::write_bytes(dst as *mut u8, c as u8, n as usize);
+    }
 }
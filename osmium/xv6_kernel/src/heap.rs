@@ -0,0 +1,295 @@
+//! Kernel heap for Rust code, backed by `kalloc()`.
+//!
+//! Small allocations come from size classes of 16 to 2048 bytes, each a power of two. A class
+//! keeps a free list of objects carved out of whole pages (slabs) from `kalloc()`, and takes
+//! another page when the list runs out. Slabs are page aligned, so every object is aligned to its
+//! size. Allocations larger than the largest class get a page to themselves, and allocations
+//! larger than a page fail, since `kalloc()` has no way to hand out contiguous pages. A slab stays
+//! with its class once allocated.
+//!
+//! The heap is the `#[global_allocator]`, so kernel code can use `Box`, `Vec` and `BTreeMap`. When
+//! memory runs out, their infallible methods (`Box::new`, `Vec::push`, ...) panic the kernel, so
+//! code that runs on behalf of a system call should allocate with `try_box`, `try_vec` or
+//! `Vec::try_reserve` and fail the call with `-ENOMEM` instead.
+
+use crate::c_defs::*;
+use crate::kalloc::{kalloc, kfree};
+use crate::riscv;
+use alloc::alloc::{alloc, Layout};
+use alloc::boxed::Box;
+use alloc::collections::TryReserveError;
+use alloc::vec::Vec;
+use core::alloc::GlobalAlloc;
+use core::ffi::c_void;
+use core::mem::{self, MaybeUninit};
+use core::ptr;
+use xv6_defs::c_structs::*;
+use xv6_defs::errno::ENOMEM;
+
+/// Size of the smallest class.
+const MIN_SIZE: usize = 16;
+/// Number of size classes. The largest is `MIN_SIZE << (NCLASS - 1)` bytes.
+const NCLASS: usize = 8;
+
+const PGSIZE: usize = riscv::PGSIZE as usize;
+
+/// Where an allocation comes from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Source {
+    /// Size class `i`, whose objects are `MIN_SIZE << i` bytes.
+    Class(usize),
+    /// A page of its own.
+    Page,
+    /// Nowhere: larger than a page.
+    TooBig,
+}
+
+impl Source {
+    fn of(layout: &Layout) -> Source {
+        let size = layout.size().max(layout.align()).max(MIN_SIZE);
+        let class = (size.next_power_of_two() / MIN_SIZE).trailing_zeros() as usize;
+        if class < NCLASS {
+            Source::Class(class)
+        } else if size <= PGSIZE {
+            Source::Page
+        } else {
+            Source::TooBig
+        }
+    }
+}
+
+/// A free object, which holds the free list link.
+struct Object {
+    next: *mut Object,
+}
+
+struct SizeClass {
+    lock: SpinLock,
+    free: *mut Object,
+    /// Size of the objects.
+    size: usize,
+    /// Number of pages carved into objects.
+    nslab: usize,
+}
+
+impl SizeClass {
+    /// Takes an object off the free list. Returns null if the list is empty.
+    unsafe fn alloc(&mut self) -> *mut u8 {
+        acquire(&mut self.lock);
+        let o = self.free;
+        if !o.is_null() {
+            self.free = (*o).next;
+        }
+        release(&mut self.lock);
+        o as *mut u8
+    }
+
+    /// Puts the object at `p` back on the free list.
+    unsafe fn free(&mut self, p: *mut u8) {
+        let o = p as *mut Object;
+        acquire(&mut self.lock);
+        (*o).next = self.free;
+        self.free = o;
+        release(&mut self.lock);
+    }
+
+    /// Carves `page` into objects and returns the first one, putting the rest on the free list.
+    unsafe fn refill(&mut self, page: *mut u8) -> *mut u8 {
+        acquire(&mut self.lock);
+        for off in (self.size..PGSIZE).step_by(self.size).rev() {
+            let o = page.add(off) as *mut Object;
+            (*o).next = self.free;
+            self.free = o;
+        }
+        self.nslab += 1;
+        release(&mut self.lock);
+        page
+    }
+}
+
+static mut CLASSES: MaybeUninit<[SizeClass; NCLASS]> = MaybeUninit::uninit();
+
+unsafe fn classes() -> &'static mut [SizeClass; NCLASS] {
+    (*ptr::addr_of_mut!(CLASSES)).assume_init_mut()
+}
+
+/// Sets up the size classes. Called by `kinit()`, before anything can allocate.
+pub unsafe fn init() {
+    for (i, class) in classes().iter_mut().enumerate() {
+        *class = SizeClass {
+            lock: mem::zeroed(),
+            free: ptr::null_mut(),
+            size: MIN_SIZE << i,
+            nslab: 0,
+        };
+        initlock(&mut class.lock, "kheap\0".as_ptr());
+    }
+}
+
+/// The `GlobalAlloc` for the kernel heap.
+#[cfg_attr(test, allow(dead_code))]
+pub struct KernelHeap;
+
+#[cfg(not(test))]
+#[global_allocator]
+static HEAP: KernelHeap = KernelHeap;
+
+unsafe impl GlobalAlloc for KernelHeap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        match Source::of(&layout) {
+            Source::Class(i) => {
+                let class = &mut classes()[i];
+                let p = class.alloc();
+                if !p.is_null() {
+                    return p;
+                }
+                let page = kalloc() as *mut u8;
+                if page.is_null() {
+                    return ptr::null_mut();
+                }
+                class.refill(page)
+            }
+            Source::Page => kalloc() as *mut u8,
+            Source::TooBig => ptr::null_mut(),
+        }
+    }
+
+    unsafe fn dealloc(&self, p: *mut u8, layout: Layout) {
+        match Source::of(&layout) {
+            Source::Class(i) => classes()[i].free(p),
+            Source::Page => kfree(p as *mut c_void),
+            Source::TooBig => panic("kheap dealloc\0".as_ptr()),
+        }
+    }
+}
+
+/// The kernel heap is out of memory.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AllocError;
+
+// Nothing allocates on behalf of a system call yet.
+#[allow(dead_code)]
+impl AllocError {
+    /// Returns the value for a system call to return, `-ENOMEM`.
+    pub const fn errno(self) -> i32 {
+        -ENOMEM
+    }
+}
+
+impl From<TryReserveError> for AllocError {
+    fn from(_: TryReserveError) -> Self {
+        AllocError
+    }
+}
+
+/// Moves `x` into a new `Box`, or returns an error if there is no memory for it.
+#[allow(dead_code)]
+pub fn try_box<T>(x: T) -> Result<Box<T>, AllocError> {
+    let layout = Layout::new::<T>();
+    if layout.size() == 0 {
+        return Ok(Box::new(x));
+    }
+    unsafe {
+        let p = alloc(layout) as *mut T;
+        if p.is_null() {
+            return Err(AllocError);
+        }
+        p.write(x);
+        Ok(Box::from_raw(p))
+    }
+}
+
+/// Returns an empty `Vec` with room for `capacity` elements, or an error if there is no memory
+/// for them.
+#[allow(dead_code)]
+pub fn try_vec<T>(capacity: usize) -> Result<Vec<T>, AllocError> {
+    let mut v = Vec::new();
+    v.try_reserve_exact(capacity)?;
+    Ok(v)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[repr(C, align(4096))]
+    struct Page([u8; PGSIZE]);
+
+    fn class(size: usize) -> SizeClass {
+        let mut class = SizeClass {
+            lock: unsafe { mem::zeroed() },
+            free: ptr::null_mut(),
+            size,
+            nslab: 0,
+        };
+        unsafe { initlock(&mut class.lock, "kheap\0".as_ptr()) };
+        class
+    }
+
+    #[test]
+    fn source_of_layout() {
+        let source = |size, align| Source::of(&Layout::from_size_align(size, align).unwrap());
+        assert_eq!(source(0, 1), Source::Class(0));
+        assert_eq!(source(1, 1), Source::Class(0));
+        assert_eq!(source(16, 8), Source::Class(0));
+        assert_eq!(source(17, 8), Source::Class(1));
+        assert_eq!(source(8, 64), Source::Class(2));
+        assert_eq!(source(2048, 8), Source::Class(NCLASS - 1));
+        assert_eq!(source(2049, 8), Source::Page);
+        assert_eq!(source(8, 4096), Source::Page);
+        assert_eq!(source(4096, 8), Source::Page);
+        assert_eq!(source(4097, 8), Source::TooBig);
+        assert_eq!(source(8, 8192), Source::TooBig);
+    }
+
+    #[test]
+    fn refill_carves_page() {
+        let mut page = Box::new(Page([0; PGSIZE]));
+        let base = page.0.as_mut_ptr();
+        let mut class = class(512);
+        let mut objects = HashSet::new();
+        unsafe {
+            assert!(class.alloc().is_null());
+            assert_eq!(class.refill(base), base);
+            objects.insert(base as usize);
+            loop {
+                let p = class.alloc();
+                if p.is_null() {
+                    break;
+                }
+                assert_eq!(p as usize % 512, 0);
+                assert!(p >= base && p < base.add(PGSIZE));
+                assert!(objects.insert(p as usize));
+            }
+        }
+        assert_eq!(objects.len(), PGSIZE / 512);
+        assert_eq!(class.nslab, 1);
+        assert_eq!(class.lock.locked, 0);
+    }
+
+    #[test]
+    fn free_then_alloc_is_lifo() {
+        let mut page = Box::new(Page([0; PGSIZE]));
+        let mut class = class(2048);
+        unsafe {
+            let a = class.refill(page.0.as_mut_ptr());
+            let b = class.alloc();
+            assert!(class.alloc().is_null());
+            class.free(a);
+            class.free(b);
+            assert_eq!(class.alloc(), b);
+            assert_eq!(class.alloc(), a);
+            assert!(class.alloc().is_null());
+        }
+    }
+
+    #[test]
+    fn try_helpers() {
+        assert_eq!(*try_box(7u64).unwrap(), 7);
+        assert!(try_box(()).is_ok());
+        assert!(try_vec::<u32>(100).unwrap().capacity() >= 100);
+        assert_eq!(try_vec::<u64>(usize::MAX).unwrap_err(), AllocError);
+        assert_eq!(AllocError.errno(), -ENOMEM);
+    }
+}
@@ -2,95 +2,427 @@
 //!
 //! Physical memory allocator, for user processes, kernel stacks, page-table pages, and pipe
 //! buffers. Allocates whole 4096-byte pages.
+//!
+//! Pages are reference counted so that processes can share them after `fork`, see `vm.rs`. A page
+//! from `kalloc` has one reference, `kref_inc` adds one, and `kfree` drops one and only frees the
+//! page when none are left.
 
 use crate::c_defs::*;
+use crate::heap;
 use crate::memlayout;
 use crate::riscv;
 use core::ffi::c_void;
 use core::mem::MaybeUninit;
+use core::ptr;
+use core::sync::atomic::{AtomicU32, Ordering};
 use xv6_defs::c_structs::*;
-use xv6_defs::c_types::*;
-
-extern "C" {
-    // First address after the kernel. Defined by kernel.ld.
-    static mut end: c_char;
-}
+use xv6_defs::param::NCPU;
 
 struct Run {
     next: *mut Run,
 }
 
+/// The free pages of one CPU.
 struct KMem {
     lock: SpinLock,
     freelist: *mut Run,
     nfree: u64,
 }
 
-static mut KMEM: MaybeUninit<KMem> = MaybeUninit::uninit();
+impl KMem {
+    /// Fills the page at `pa` with junk and puts it on the free list.
+    unsafe fn free(&mut self, pa: *mut c_void) {
+        // Fill with junk to catch dangling refs.
+        memset(pa, 1, riscv::PGSIZE as u32);
+
+        let r = pa as *mut Run;
+
+        acquire(&mut self.lock);
+        (*r).next = self.freelist;
+        self.freelist = r;
+        self.nfree += 1;
+        release(&mut self.lock);
+    }
+
+    /// Takes a page off the free list and fills it with junk. Returns null if the list is empty.
+    unsafe fn alloc(&mut self) -> *mut c_void {
+        acquire(&mut self.lock);
+        let r = self.freelist;
+        if !r.is_null() {
+            self.freelist = (*r).next;
+            self.nfree -= 1;
+        }
+        release(&mut self.lock);
+        junk(r)
+    }
+
+    /// Takes up to `max` pages off the free list. Returns them as a null terminated list, with its
+    /// length.
+    unsafe fn take(&mut self, max: u64) -> (*mut Run, u64) {
+        acquire(&mut self.lock);
+        let first = self.freelist;
+        let mut n = 0;
+        while n < max && !self.freelist.is_null() {
+            let r = self.freelist;
+            self.freelist = (*r).next;
+            if n + 1 == max || self.freelist.is_null() {
+                (*r).next = ptr::null_mut();
+            }
+            n += 1;
+        }
+        self.nfree -= n;
+        release(&mut self.lock);
+        (first, n)
+    }
+
+    /// Puts a list of `n` pages returned by `take` on the free list.
+    unsafe fn put(&mut self, first: *mut Run, n: u64) {
+        if n == 0 {
+            return;
+        }
+        let mut last = first;
+        while !(*last).next.is_null() {
+            last = (*last).next;
+        }
+        acquire(&mut self.lock);
+        (*last).next = self.freelist;
+        self.freelist = first;
+        self.nfree += n;
+        release(&mut self.lock);
+    }
+}
+
+/// Fills the page `r`, if not null, with junk for whoever allocated it.
+unsafe fn junk(r: *mut Run) -> *mut c_void {
+    if !r.is_null() {
+        memset(r as *mut c_void, 5, riscv::PGSIZE as u32);
+    }
+    r as *mut c_void
+}
+
+/// Most pages one CPU takes from another at once when it runs out.
+const STEAL: u64 = 64;
+
+/// Per-CPU free lists, so CPUs don't contend for one lock. A CPU frees pages to its own list and
+/// allocates from it, taking some pages from other CPUs' lists when it runs out.
+struct KAlloc {
+    cpus: [KMem; NCPU],
+}
+
+impl KAlloc {
+    /// Allocates a page for CPU `id`.
+    unsafe fn alloc(&mut self, id: usize) -> *mut c_void {
+        let pa = self.cpus[id].alloc();
+        if !pa.is_null() {
+            return pa;
+        }
+        for i in 1..NCPU {
+            // Never hold two CPUs' locks at once, or two CPUs stealing from each other could
+            // deadlock.
+            let (first, n) = self.cpus[(id + i) % NCPU].take(STEAL);
+            if n > 0 {
+                // Keep the first page, so other CPUs can't take it between putting the pages on
+                // our list and allocating from it.
+                self.cpus[id].put((*first).next, n - 1);
+                return junk(first);
+            }
+        }
+        ptr::null_mut()
+    }
+
+    /// Total number of free pages. Not exact while other CPUs allocate and free.
+    fn nfree(&self) -> u64 {
+        self.cpus.iter().map(|kmem| kmem.nfree).sum()
+    }
+}
+
+/// Lock names `kmem_0`, `kmem_1`, ..., which `sys_ntas` reports as kmem locks.
+static LOCK_NAMES: [[u8; 8]; NCPU] = lock_names();
+
+// The names have room for one digit.
+const _: () = assert!(NCPU <= 10);
+
+const fn lock_names() -> [[u8; 8]; NCPU] {
+    let mut names = [*b"kmem_0\0\0"; NCPU];
+    let mut i = 0;
+    while i < NCPU {
+        names[i][5] = b'0' + i as u8;
+        i += 1;
+    }
+    names
+}
+
+static mut KMEM: MaybeUninit<KAlloc> = MaybeUninit::uninit();
+
+/// Number of pages of physical memory.
+const NPAGES: usize = ((memlayout::PHYSTOP - memlayout::KERNBASE) / riscv::PGSIZE) as usize;
+
+/// Reference counts of the pages from `KERNBASE` to `PHYSTOP`.
+static REFS: [AtomicU32; NPAGES] = [const { AtomicU32::new(0) }; NPAGES];
+
+/// Returns the reference count of the page at `pa`.
+fn page_ref(pa: u64) -> &'static AtomicU32 {
+    if !pa.is_multiple_of(riscv::PGSIZE) || !(memlayout::KERNBASE..memlayout::PHYSTOP).contains(&pa)
+    {
+        unsafe { panic("page_ref\0".as_ptr()) };
+    }
+    &REFS[((pa - memlayout::KERNBASE) / riscv::PGSIZE) as usize]
+}
+
+/// Returns the number of references to the page at `pa`.
+pub fn refcount(pa: u64) -> u32 {
+    page_ref(pa).load(Ordering::Acquire)
+}
+
+/// Drops a reference to the page at `pa`. Returns whether it was the last one.
+fn drop_ref(pa: u64) -> bool {
+    match page_ref(pa).fetch_sub(1, Ordering::AcqRel) {
+        0 => unsafe { panic("kfree: page not allocated\0".as_ptr()) },
+        1 => true,
+        _ => false,
+    }
+}
+
+/// Adds a reference to the page at `pa`, which must have been returned by `kalloc()`.
+#[no_mangle]
+pub unsafe extern "C" fn kref_inc(pa: *mut c_void) {
+    if page_ref(pa as u64).fetch_add(1, Ordering::AcqRel) == 0 {
+        panic("kref_inc\0".as_ptr());
+    }
+}
+
+/// Returns the current CPU's id.
+unsafe fn mycpuid() -> usize {
+    // cpuid() is only stable with interrupts off. Moving to another CPU afterwards is harmless:
+    // the caller just uses that CPU's list, under its lock.
+    push_off();
+    let id = cpuid();
+    pop_off();
+    id as usize
+}
 
+/// Sets up the page allocator and the kernel heap on top of it. All free pages start out on the
+/// list of the CPU that calls this; the others take pages from it as they need them.
 #[no_mangle]
 pub unsafe extern "C" fn kinit() {
-    initlock(&mut KMEM.assume_init_mut().lock, "kmem\0".as_ptr());
+    let cpus = &mut (*ptr::addr_of_mut!(KMEM)).assume_init_mut().cpus;
+    for (kmem, name) in cpus.iter_mut().zip(&LOCK_NAMES) {
+        kmem.freelist = ptr::null_mut();
+        kmem.nfree = 0;
+        initlock(&mut kmem.lock, name.as_ptr());
+    }
     freerange(
-        &mut end as *mut u8 as *mut c_void,
+        ptr::addr_of_mut!(end) as *mut c_void,
         memlayout::PHYSTOP as *mut c_void,
     );
+    heap::init();
 }
 
 unsafe fn freerange(pa_start: *mut c_void, pa_end: *mut c_void) {
     let mut p = riscv::pgroundup(pa_start as u64);
     while p + riscv::PGSIZE <= pa_end as u64 {
+        // As if the page had been allocated.
+        page_ref(p).store(1, Ordering::Relaxed);
         kfree(p as *mut c_void);
         p += riscv::PGSIZE;
     }
 }
 
-/// Free the page of physical memory pointed at by pa, which normally should have been returned by a
-/// call to kalloc().  (The exception is when initializing the allocator; see kinit above.)
+/// Drop a reference to the page of physical memory pointed at by pa, which normally should have
+/// been returned by a call to kalloc(), and free it if that was the last reference.  (The exception
+/// is when initializing the allocator; see kinit above.)
 #[no_mangle]
 pub unsafe extern "C" fn kfree(pa: *mut c_void) {
-    if (pa as u64) % riscv::PGSIZE != 0
-        || pa < end as *mut c_void
+    if !(pa as u64).is_multiple_of(riscv::PGSIZE)
+        || pa < ptr::addr_of_mut!(end) as *mut c_void
         || pa as u64 >= memlayout::PHYSTOP
     {
-        panic("kfree".as_ptr());
+        panic("kfree\0".as_ptr());
+    }
+    if !drop_ref(pa as u64) {
+        return;
     }
 
-    // Fill with junk to catch dangling refs.
-    memset(pa, 1, riscv::PGSIZE as u32);
-
-    let r = pa as *mut Run;
-
-    let kmem = KMEM.assume_init_mut();
-    acquire(&mut kmem.lock);
-    (*r).next = kmem.freelist;
-    kmem.freelist = r;
-    kmem.nfree += 1;
-    release(&mut kmem.lock);
+    (*ptr::addr_of_mut!(KMEM)).assume_init_mut().cpus[mycpuid()].free(pa);
 }
 
 /// Allocate one 4096-byte page of physical memory. Returns a pointer that the kernel can use.
 /// Returns 0 if the memory cannot be allocated.
 #[no_mangle]
 pub unsafe extern "C" fn kalloc() -> *mut c_void {
-    let kmem = KMEM.assume_init_mut();
-    acquire(&mut kmem.lock);
-    let r = kmem.freelist;
-    if !r.is_null() {
-        kmem.freelist = (*r).next;
-        kmem.nfree -= 1;
+    let pa = (*ptr::addr_of_mut!(KMEM))
+        .assume_init_mut()
+        .alloc(mycpuid());
+    if !pa.is_null() {
+        page_ref(pa as u64).store(1, Ordering::Release);
     }
-    release(&mut kmem.lock);
-
-    if !r.is_null() {
-        // fill with junk
-        memset(r as *mut c_void, 5, riscv::PGSIZE as u32);
-    }
-
-    r as *mut c_void
+    pa
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn sys_nfree() -> u64 {
-    KMEM.assume_init_ref().nfree
+    (*ptr::addr_of!(KMEM)).assume_init_ref().nfree()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec::Vec;
+
+    #[repr(C, align(4096))]
+    struct Page([u8; riscv::PGSIZE as usize]);
+
+    fn pages(n: usize) -> Vec<Page> {
+        (0..n).map(|_| Page([0; riscv::PGSIZE as usize])).collect()
+    }
+
+    fn kmem() -> KMem {
+        let mut kmem = KMem {
+            lock: unsafe { core::mem::zeroed() },
+            freelist: ptr::null_mut(),
+            nfree: 0,
+        };
+        unsafe { initlock(&mut kmem.lock, "kmem\0".as_ptr()) };
+        kmem
+    }
+
+    fn kalloc() -> KAlloc {
+        KAlloc {
+            cpus: [(); NCPU].map(|_| kmem()),
+        }
+    }
+
+    fn addr(page: &mut Page) -> *mut c_void {
+        page as *mut Page as *mut c_void
+    }
+
+    #[test]
+    fn alloc_empty() {
+        let mut kmem = kmem();
+        assert!(unsafe { kmem.alloc() }.is_null());
+        assert_eq!(kmem.nfree, 0);
+        assert_eq!(kmem.lock.locked, 0);
+    }
+
+    #[test]
+    fn free_then_alloc_is_lifo() {
+        let mut mem = pages(3);
+        let mut kmem = kmem();
+        let addrs: Vec<_> = mem.iter_mut().map(addr).collect();
+        unsafe {
+            for &pa in &addrs {
+                kmem.free(pa);
+            }
+            assert_eq!(kmem.nfree, 3);
+            assert_eq!(kmem.alloc(), addrs[2]);
+            assert_eq!(kmem.alloc(), addrs[1]);
+            assert_eq!(kmem.nfree, 1);
+            kmem.free(addrs[2]);
+            assert_eq!(kmem.alloc(), addrs[2]);
+            assert_eq!(kmem.alloc(), addrs[0]);
+            assert!(kmem.alloc().is_null());
+        }
+        assert_eq!(kmem.nfree, 0);
+        assert_eq!(kmem.lock.locked, 0);
+    }
+
+    #[test]
+    fn pages_are_filled_with_junk() {
+        let mut mem = pages(1);
+        let mut kmem = kmem();
+        unsafe {
+            kmem.free(addr(&mut mem[0]));
+            // The start of a free page holds the list link.
+            let link = core::mem::size_of::<Run>();
+            assert!(mem[0].0[link..].iter().all(|&b| b == 1));
+            kmem.alloc();
+        }
+        assert!(mem[0].0.iter().all(|&b| b == 5));
+    }
+
+    #[test]
+    #[should_panic(expected = "acquire")]
+    fn free_with_lock_held() {
+        let mut mem = pages(1);
+        let mut kmem = kmem();
+        unsafe {
+            acquire(&mut kmem.lock);
+            kmem.free(addr(&mut mem[0]));
+        }
+    }
+
+    #[test]
+    fn take_and_put() {
+        let mut mem = pages(3);
+        let mut a = kmem();
+        let mut b = kmem();
+        unsafe {
+            for page in mem.iter_mut() {
+                a.free(addr(page));
+            }
+            let (first, n) = a.take(2);
+            assert_eq!((n, a.nfree), (2, 1));
+            b.put(first, n);
+            assert_eq!(b.nfree, 2);
+            assert_eq!(b.alloc(), addr(&mut mem[2]));
+            assert_eq!(b.alloc(), addr(&mut mem[1]));
+            assert!(b.alloc().is_null());
+
+            let (first, n) = a.take(STEAL);
+            assert_eq!((n, a.nfree), (1, 0));
+            b.put(first, n);
+            assert_eq!(b.alloc(), addr(&mut mem[0]));
+            assert_eq!(a.take(STEAL).1, 0);
+        }
+        assert_eq!(a.lock.locked, 0);
+        assert_eq!(b.lock.locked, 0);
+    }
+
+    #[test]
+    fn alloc_steals_from_other_cpus() {
+        let mut mem = pages(STEAL as usize + 2);
+        let mut kmem = kalloc();
+        unsafe {
+            for page in mem.iter_mut() {
+                kmem.cpus[3].free(addr(page));
+            }
+            assert_eq!(kmem.nfree(), STEAL + 2);
+
+            assert!(!kmem.alloc(1).is_null());
+            assert_eq!(kmem.cpus[1].nfree, STEAL - 1);
+            assert_eq!(kmem.cpus[3].nfree, 2);
+
+            // CPU 1 uses its own pages before taking the rest of CPU 3's.
+            for _ in 0..STEAL + 1 {
+                assert!(!kmem.alloc(1).is_null());
+            }
+            assert_eq!(kmem.cpus[3].nfree, 0);
+            assert!(kmem.alloc(1).is_null());
+            assert!(kmem.alloc(0).is_null());
+
+            // A single page is stolen and returned straight away.
+            kmem.cpus[5].free(addr(&mut mem[0]));
+            assert_eq!(kmem.alloc(2), addr(&mut mem[0]));
+        }
+        assert_eq!(kmem.nfree(), 0);
+        assert!(kmem.cpus.iter().all(|kmem| kmem.lock.locked == 0));
+    }
+
+    #[test]
+    fn lock_names() {
+        assert_eq!(&LOCK_NAMES[0], b"kmem_0\0\0");
+        assert_eq!(&LOCK_NAMES[NCPU - 1][..6], b"kmem_7");
+    }
+
+    #[test]
+    fn page_refs() {
+        let pa = memlayout::KERNBASE + 5 * riscv::PGSIZE;
+        page_ref(pa).store(1, Ordering::Relaxed);
+        unsafe { kref_inc(pa as *mut c_void) };
+        assert_eq!(refcount(pa), 2);
+        assert!(!drop_ref(pa));
+        assert!(drop_ref(pa));
+        assert_eq!(refcount(pa), 0);
+        assert_eq!(refcount(memlayout::PHYSTOP - riscv::PGSIZE), 0);
+    }
 }
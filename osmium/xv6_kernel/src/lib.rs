@@ -1,13 +1,26 @@
 //! # xv6_kernel
 //! The Rust components of the xv6_kernel.
 
-#![no_std]
+#![cfg_attr(not(test), no_std)]
 
-extern crate panic_halt;
+extern crate alloc;
 
+// Declared first so the print macros can be used in the other modules.
+#[macro_use]
+mod printf;
+
+mod buddy;
 mod c_defs;
+mod heap;
 mod kalloc;
+mod list;
 mod memlayout;
+#[cfg(not(test))]
+mod panic;
 mod riscv;
 mod string;
+// System call glue, which needs the rest of the kernel.
+#[cfg(not(test))]
 mod sysproc;
+#[cfg(not(test))]
+mod vm;
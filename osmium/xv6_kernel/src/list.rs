@@ -0,0 +1,159 @@
+//! Rust version of the list that was kernel/list.c.
+//!
+//! Doubly linked, circular list whose links live inside the elements, so putting something on a
+//! list never allocates. Doubly linked makes remove fast. Circular simplifies code, because there
+//! is no need to check for an empty list in insert and remove.
+
+use core::marker::PhantomData;
+use core::ptr;
+
+/// Links to the next and previous elements, the same as C's `struct list`.
+#[repr(C)]
+pub struct Link {
+    next: *mut Link,
+    prev: *mut Link,
+}
+
+/// A type that can be put on a `List`.
+///
+/// # Safety
+///
+/// The type must be `#[repr(C)]` with a `Link` as its first field, so a pointer to the element is
+/// also a pointer to its link.
+pub unsafe trait Node {}
+
+/// A list of `T`s, linked through the `Link` at the start of each element.
+///
+/// Lists have no constructor: they are set up in place with `init`, because the elements point
+/// back at the head. A list must not move once it has been initialized.
+#[repr(C)]
+pub struct List<T: Node> {
+    head: Link,
+    _elem: PhantomData<*mut T>,
+}
+
+impl<T: Node> List<T> {
+    /// Makes the list empty.
+    pub unsafe fn init(&mut self) {
+        let head = &mut self.head as *mut Link;
+        self.head.next = head;
+        self.head.prev = head;
+    }
+
+    fn head(&self) -> *mut Link {
+        &self.head as *const Link as *mut Link
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.head.next == self.head()
+    }
+
+    /// Adds `e` to the front of the list. `e` must not be on a list already.
+    pub unsafe fn push(&mut self, e: *mut T) {
+        let e = e as *mut Link;
+        (*e).next = self.head.next;
+        (*e).prev = self.head();
+        (*self.head.next).prev = e;
+        self.head.next = e;
+    }
+
+    /// Removes and returns the element at the front of the list, or `None` if the list is empty.
+    pub unsafe fn pop(&mut self) -> Option<*mut T> {
+        if self.is_empty() {
+            return None;
+        }
+        let e = self.head.next as *mut T;
+        Self::remove(e);
+        Some(e)
+    }
+
+    /// Removes `e` from the list it is on.
+    pub unsafe fn remove(e: *mut T) {
+        let e = e as *mut Link;
+        (*(*e).prev).next = (*e).next;
+        (*(*e).next).prev = (*e).prev;
+        (*e).next = ptr::null_mut();
+        (*e).prev = ptr::null_mut();
+    }
+
+    /// Returns an iterator over the elements, from front to back.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            list: self,
+            cur: self.head.next,
+        }
+    }
+}
+
+pub struct Iter<'a, T: Node> {
+    list: &'a List<T>,
+    cur: *mut Link,
+}
+
+impl<T: Node> Iterator for Iter<'_, T> {
+    type Item = *mut T;
+
+    fn next(&mut self) -> Option<*mut T> {
+        if self.cur == self.list.head() {
+            return None;
+        }
+        let e = self.cur as *mut T;
+        self.cur = unsafe { (*self.cur).next };
+        Some(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::mem;
+    use std::vec::Vec;
+
+    #[repr(C)]
+    struct Elem {
+        link: Link,
+        val: u32,
+    }
+
+    unsafe impl Node for Elem {}
+
+    fn elems(n: u32) -> Vec<Elem> {
+        (0..n)
+            .map(|val| Elem {
+                link: Link {
+                    next: ptr::null_mut(),
+                    prev: ptr::null_mut(),
+                },
+                val,
+            })
+            .collect()
+    }
+
+    fn vals(list: &List<Elem>) -> Vec<u32> {
+        list.iter().map(|e| unsafe { (*e).val }).collect()
+    }
+
+    #[test]
+    fn push_pop_remove() {
+        let mut elems = elems(3);
+        let mut list: List<Elem> = unsafe { mem::zeroed() };
+        unsafe {
+            list.init();
+            assert!(list.is_empty());
+            assert!(list.pop().is_none());
+
+            for e in elems.iter_mut() {
+                list.push(e);
+            }
+            assert_eq!(vals(&list), [2, 1, 0]);
+
+            List::remove(&mut elems[1] as *mut Elem);
+            assert_eq!(vals(&list), [2, 0]);
+
+            assert_eq!((*list.pop().unwrap()).val, 2);
+            assert_eq!((*list.pop().unwrap()).val, 0);
+            assert!(list.is_empty());
+            assert!(list.pop().is_none());
+        }
+    }
+}
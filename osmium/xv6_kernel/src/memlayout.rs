@@ -1,4 +1,14 @@
 //! Constants defined in `kernel/memlayout.h`
 
+use crate::riscv;
+
 pub const KERNBASE: u64 = 0x80000000;
 pub const PHYSTOP: u64 = KERNBASE + 128 * 1024 * 1024;
+
+/// The trampoline page, mapped at the highest address in both user and kernel space.
+#[cfg_attr(test, allow(dead_code))]
+pub const TRAMPOLINE: u64 = riscv::MAXVA - riscv::PGSIZE;
+
+/// The process's trapframe, just below the trampoline. User memory must end before it.
+#[cfg_attr(test, allow(dead_code))]
+pub const TRAPFRAME: u64 = TRAMPOLINE - riscv::PGSIZE;
@@ -0,0 +1,60 @@
+//! Panic handler for Rust code in the kernel.
+//!
+//! The panic message and location are formatted into a buffer on the stack and passed to the C
+//! `panic()`, which prints them with a backtrace and freezes the other CPUs, the same as a panic
+//! in the C code.
+
+use crate::c_defs::panic;
+use core::fmt::{self, Write};
+use core::panic::PanicInfo;
+use core::slice;
+
+/// Size of the message buffer, including the null terminator. Longer messages are cut off.
+const BUFSIZE: usize = 256;
+
+/// Null terminated message for `panic()`. The message is used as a `printf` format, so `%` is
+/// escaped.
+struct Message {
+    buf: [u8; BUFSIZE],
+    len: usize,
+}
+
+impl Write for Message {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for &c in s.as_bytes() {
+            let escaped: &[u8] = if c == b'%' {
+                b"%%"
+            } else {
+                slice::from_ref(&c)
+            };
+            // Leave room for the null terminator.
+            if self.len + escaped.len() >= BUFSIZE {
+                return Err(fmt::Error);
+            }
+            self.buf[self.len..self.len + escaped.len()].copy_from_slice(escaped);
+            self.len += escaped.len();
+        }
+        Ok(())
+    }
+}
+
+#[panic_handler]
+fn rust_panic(info: &PanicInfo) -> ! {
+    let mut msg = Message {
+        buf: [0; BUFSIZE],
+        len: 0,
+    };
+    // A message that doesn't fit is printed up to where it was cut off.
+    let _ = match info.location() {
+        Some(loc) => write!(
+            msg,
+            "rust: {} at {}:{}",
+            info.message(),
+            loc.file(),
+            loc.line()
+        ),
+        None => write!(msg, "rust: {}", info.message()),
+    };
+    msg.buf[msg.len] = 0;
+    unsafe { panic(msg.buf.as_ptr()) }
+}
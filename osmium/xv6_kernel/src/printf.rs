@@ -0,0 +1,36 @@
+//! Formatted console output for Rust code in the kernel, the counterpart of `printf()` in
+//! kernel/printf.c.
+//!
+//! Characters go straight to `consputc()` without taking the `printf()` lock, so output from
+//! several CPUs at once may interleave. Meant for debugging output such as `bd_print()`.
+
+use crate::c_defs::consputc;
+use core::fmt::{self, Write};
+use xv6_defs::c_types::c_int;
+
+struct Console;
+
+impl Write for Console {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for &c in s.as_bytes() {
+            unsafe { consputc(c as c_int) };
+        }
+        Ok(())
+    }
+}
+
+#[doc(hidden)]
+pub fn _print(args: fmt::Arguments) {
+    let _ = Console.write_fmt(args);
+}
+
+/// Prints to the console.
+macro_rules! print {
+    ($($arg:tt)*) => ($crate::printf::_print(format_args!($($arg)*)));
+}
+
+/// Prints to the console, with a newline.
+macro_rules! println {
+    () => (print!("\n"));
+    ($($arg:tt)*) => ($crate::printf::_print(format_args!("{}\n", format_args!($($arg)*))));
+}
@@ -5,3 +5,57 @@ pub const PGSIZE: u64 = 4096;
 pub fn pgroundup(sz: u64) -> u64 {
     (sz + PGSIZE - 1) & !(PGSIZE - 1)
 }
+
+/// Valid.
+pub const PTE_V: u64 = 1 << 0;
+// Used by vm.rs, which isn't built for tests.
+#[cfg_attr(test, allow(dead_code))]
+pub const PTE_R: u64 = 1 << 1;
+#[cfg_attr(test, allow(dead_code))]
+pub const PTE_W: u64 = 1 << 2;
+#[cfg_attr(test, allow(dead_code))]
+pub const PTE_X: u64 = 1 << 3;
+/// User can access.
+pub const PTE_U: u64 = 1 << 4;
+/// Copy-on-write. One of the bits reserved for software.
+pub const PTE_COW: u64 = 1 << 8;
+
+/// Shifts a physical address to the right place for a PTE.
+pub fn pa2pte(pa: u64) -> u64 {
+    (pa >> 12) << 10
+}
+
+pub fn pte2pa(pte: u64) -> u64 {
+    (pte >> 10) << 12
+}
+
+pub fn pte_flags(pte: u64) -> u64 {
+    pte & 0x3FF
+}
+
+/// One beyond the highest possible virtual address. MAXVA is actually one bit less than the max
+/// allowed by Sv39, to avoid having to sign-extend virtual addresses that have the high bit set.
+#[cfg_attr(test, allow(dead_code))]
+pub const MAXVA: u64 = 1 << (9 + 9 + 9 + 12 - 1);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pgroundup_rounds_to_page_boundary() {
+        assert_eq!(pgroundup(0), 0);
+        assert_eq!(pgroundup(1), PGSIZE);
+        assert_eq!(pgroundup(PGSIZE - 1), PGSIZE);
+        assert_eq!(pgroundup(PGSIZE), PGSIZE);
+        assert_eq!(pgroundup(PGSIZE + 1), 2 * PGSIZE);
+        assert_eq!(pgroundup(0x8000_0123), 0x8000_1000);
+    }
+
+    #[test]
+    fn pte_round_trip() {
+        let pte = pa2pte(0x8765_4000) | PTE_V | PTE_U | PTE_COW;
+        assert_eq!(pte2pa(pte), 0x8765_4000);
+        assert_eq!(pte_flags(pte), PTE_V | PTE_U | PTE_COW);
+    }
+}
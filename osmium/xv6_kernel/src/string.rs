@@ -3,9 +3,10 @@
 use xv6_defs::c_types::*;
 
 // Note, this doesn't define every function that was previously defined in string.c in xv6. This is
-// because functions such as memcpy are builtin by LLVM.
+// because functions such as memcpy are builtin by LLVM. The names are left mangled in host unit
+// tests, where they would otherwise replace the host C library's functions.
 
-#[no_mangle]
+#[cfg_attr(not(test), no_mangle)]
 pub unsafe extern "C" fn strncmp(p: *const c_char, q: *const c_char, n: c_uint) -> c_int {
     let mut p1 = p;
     let mut p2 = q;
@@ -21,7 +22,7 @@ pub unsafe extern "C" fn strncmp(p: *const c_char, q: *const c_char, n: c_uint)
     *p1 as c_int - *p2 as c_int
 }
 
-#[no_mangle]
+#[cfg_attr(not(test), no_mangle)]
 pub unsafe extern "C" fn strncpy(s: *mut c_char, t: *const c_char, n: c_int) -> *mut c_char {
     let mut i = 0;
     while i < n {
@@ -39,10 +40,14 @@ pub unsafe extern "C" fn strncpy(s: *mut c_char, t: *const c_char, n: c_int) ->
     s
 }
 
-#[no_mangle]
+#[cfg_attr(not(test), no_mangle)]
 pub unsafe extern "C" fn safestrcpy(s: *mut c_char, t: *const c_char, n: c_int) -> *mut c_char {
+    if n <= 0 {
+        return s;
+    }
+    // Copy at most n - 1 bytes, leaving room for the terminator.
     let mut i = 0;
-    while i < n {
+    while i < n - 1 {
         let b = *t.add(i as usize);
         *s.add(i as usize) = b;
         i += 1;
@@ -54,7 +59,7 @@ pub unsafe extern "C" fn safestrcpy(s: *mut c_char, t: *const c_char, n: c_int)
     s
 }
 
-#[no_mangle]
+#[cfg_attr(not(test), no_mangle)]
 pub unsafe extern "C" fn strlen(s: *const c_char) -> c_int {
     let mut len: c_int = 0;
     let mut c = *s.add(len as usize);
@@ -64,3 +69,57 @@ pub unsafe extern "C" fn strlen(s: *const c_char) -> c_int {
     }
     len
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ptr(s: &[u8]) -> *const c_char {
+        s.as_ptr() as *const c_char
+    }
+
+    #[test]
+    fn strncmp_compares_up_to_n() {
+        unsafe {
+            assert_eq!(strncmp(ptr(b"abc\0"), ptr(b"abc\0"), 10), 0);
+            assert!(strncmp(ptr(b"abc\0"), ptr(b"abd\0"), 10) < 0);
+            assert!(strncmp(ptr(b"abd\0"), ptr(b"abc\0"), 10) > 0);
+            assert_eq!(strncmp(ptr(b"abc\0"), ptr(b"abd\0"), 2), 0);
+            assert!(strncmp(ptr(b"ab\0"), ptr(b"abc\0"), 10) < 0);
+            assert_eq!(strncmp(ptr(b"x\0"), ptr(b"y\0"), 0), 0);
+        }
+    }
+
+    #[test]
+    fn strncpy_pads_with_nulls() {
+        let mut buf = [b'x' as c_char; 6];
+        unsafe { strncpy(buf.as_mut_ptr(), ptr(b"ab\0"), 5) };
+        assert_eq!(buf, [b'a', b'b', 0, 0, 0, b'x'].map(|b| b as c_char));
+    }
+
+    #[test]
+    fn strncpy_does_not_terminate_long_strings() {
+        let mut buf = [b'x' as c_char; 4];
+        unsafe { strncpy(buf.as_mut_ptr(), ptr(b"abcdef\0"), 3) };
+        assert_eq!(buf, [b'a', b'b', b'c', b'x'].map(|b| b as c_char));
+    }
+
+    #[test]
+    fn safestrcpy_stays_within_n() {
+        let mut buf = [b'x' as c_char; 6];
+        unsafe { safestrcpy(buf.as_mut_ptr(), ptr(b"abcdef\0"), 3) };
+        assert_eq!(buf[..4], [b'a', b'b', 0, b'x'].map(|b| b as c_char));
+        unsafe { safestrcpy(buf.as_mut_ptr(), ptr(b"z\0"), 5) };
+        assert_eq!(buf[..4], [b'z', 0, 0, b'x'].map(|b| b as c_char));
+        unsafe { safestrcpy(buf.as_mut_ptr(), ptr(b"abc\0"), 0) };
+        assert_eq!(buf[0], b'z' as c_char);
+    }
+
+    #[test]
+    fn strlen_counts_to_null() {
+        unsafe {
+            assert_eq!(strlen(ptr(b"\0")), 0);
+            assert_eq!(strlen(ptr(b"hello\0world\0")), 5);
+        }
+    }
+}
@@ -1,14 +1,16 @@
 use crate::c_defs::*;
+use crate::memlayout::TRAPFRAME;
 use core::ffi::c_void;
 use xv6_defs::c_structs::*;
 use xv6_defs::c_types::*;
+use xv6_defs::errno::*;
 
 /// Returns the current process's PID.
 #[no_mangle]
 pub unsafe extern "C" fn sys_exit() -> i32 {
     let mut n: i32 = 0;
     if argint(0, &mut n) < 0 {
-        -1
+        -EINVAL
     } else {
         exit(n);
         0
@@ -21,65 +23,95 @@ pub unsafe extern "C" fn sys_getpid() -> u64 {
     (*myproc()).pid as u64
 }
 
-/// Creates a process, returns child's PID.
+/// Creates a process, returns child's PID. Returns -EAGAIN if no process slot or memory is
+/// available.
 #[no_mangle]
 pub unsafe extern "C" fn sys_fork() -> u64 {
-    fork() as u64
+    let pid = fork();
+    if pid < 0 {
+        -EAGAIN as u64
+    } else {
+        pid as u64
+    }
 }
 
-/// Waits for a child process to exit, returns child's PID.  Return -1 if this process has no
-/// children.
+/// Waits for a child process to exit, returns child's PID. Returns -ECHILD if this process has no
+/// children, or -EINTR if it was killed while waiting.
 #[no_mangle]
 pub unsafe extern "C" fn sys_wait() -> i32 {
     let mut p: u64 = 0;
     if argaddr(0, &mut p) < 0 {
-        -1
+        return -EFAULT;
+    }
+    let pid = wait(p);
+    if pid >= 0 {
+        pid
+    } else if (*myproc()).killed != 0 {
+        -EINTR
     } else {
-        wait(p)
+        -ECHILD
     }
 }
 
 /// Grow process' memory. Returns start of new memory, or -1 if error.
+///
+/// Growing only moves the end of the process's memory; `lazyalloc()` allocates each new page when
+/// the process first touches it. Shrinking frees the pages right away.
+///
+/// Unlike the other system calls this keeps returning -1 on failure rather than a negated errno,
+/// since user code compares the result against `(char*)-1`.
 #[no_mangle]
 pub unsafe extern "C" fn sys_sbrk() -> i32 {
     let mut n: i32 = 0;
     if argint(0, &mut n) < 0 {
         return -1;
     }
-    let addr = (*myproc()).sz;
-    if growproc(n) < 0 {
-        -1
+    let p = myproc();
+    let addr = (*p).sz;
+    if n >= 0 {
+        let sz = addr + n as u64;
+        if sz > TRAPFRAME {
+            return -1;
+        }
+        (*p).sz = sz;
     } else {
-        addr as i32
+        let m = n.unsigned_abs() as u64;
+        if m > addr {
+            return -1;
+        }
+        (*p).sz = uvmdealloc((*p).pagetable, addr, addr - m);
     }
+    addr as i32
 }
 
-/// Terminates process. Returns 0, or -1 if error.
+/// Terminates process. Returns 0, or -ESRCH if there is no process with that PID.
 #[no_mangle]
 pub unsafe extern "C" fn sys_kill() -> i32 {
     let mut pid = 0;
     if argint(0, &mut pid) < 0 {
-        -1
+        -EINVAL
+    } else if kill(pid) < 0 {
+        -ESRCH
     } else {
-        kill(pid)
+        0
     }
 }
 
-/// Pause for specified number of clock ticks. Returns 0, -1 if error.
+/// Pause for specified number of clock ticks. Returns 0, or -EINTR if the process was killed.
 #[no_mangle]
 pub unsafe extern "C" fn sys_sleep() -> i32 {
     let mut n: i32 = 0;
     let ticks0: c_uint;
 
     if argint(0, &mut n) < 0 {
-        return -1;
+        return -EINVAL;
     }
     acquire(&mut tickslock);
     ticks0 = ticks;
     while ticks - ticks0 < n as u32 {
         if (*myproc()).killed != 0 {
             release(&mut tickslock);
-            return -1;
+            return -EINTR;
         }
         sleep(&mut ticks as *mut u32 as *mut c_void, &mut tickslock);
     }
@@ -0,0 +1,70 @@
+//! Virtual memory code written in Rust, used by kernel/vm.c and kernel/trap.c.
+//!
+//! `sbrk` grows a process without allocating memory (see `sys_sbrk()`). The heap pages are
+//! allocated by `lazyalloc()` when the process, or `copyin()`/`copyout()` on its behalf, first
+//! touches them.
+//!
+//! `fork` shares the parent's pages with the child instead of copying them (see `uvmcopy()`).
+//! Writable pages become read-only in both processes and are marked `PTE_COW`; the first write to
+//! one of them, by the process or by `copyout()`, comes here to give the writer a copy of its own.
+
+use crate::c_defs::*;
+use crate::kalloc::{self, kalloc, kfree};
+use crate::riscv::*;
+use core::ffi::c_void;
+use xv6_defs::c_structs::*;
+use xv6_defs::c_types::*;
+
+/// Maps a zeroed page at `va` in `pagetable`, which belongs to the current process, if `va` is
+/// below the end of the process's memory and its page has never been touched. Returns 0, or -1 if
+/// `va` is outside the process's memory or already mapped (such as the stack guard page), or there
+/// is no memory for the page.
+#[no_mangle]
+pub unsafe extern "C" fn lazyalloc(pagetable: PagetableT, va: u64) -> c_int {
+    if va >= (*myproc()).sz {
+        return -1;
+    }
+    let pte = walk(pagetable, va, 1);
+    if pte.is_null() || *pte & PTE_V != 0 {
+        return -1;
+    }
+
+    let mem = kalloc();
+    if mem.is_null() {
+        return -1;
+    }
+    memset(mem, 0, PGSIZE as c_uint);
+    *pte = pa2pte(mem as u64) | PTE_R | PTE_W | PTE_X | PTE_U | PTE_V;
+    0
+}
+
+/// Makes the copy-on-write page containing `va` in `pagetable` writable, copying it if other page
+/// tables still share it. Returns 0, or -1 if `va` isn't in a copy-on-write user page or there is
+/// no memory for the copy.
+#[no_mangle]
+pub unsafe extern "C" fn cowfault(pagetable: PagetableT, va: u64) -> c_int {
+    if va >= MAXVA {
+        return -1;
+    }
+    let pte = walk(pagetable, va, 0);
+    if pte.is_null() || *pte & (PTE_V | PTE_U | PTE_COW) != PTE_V | PTE_U | PTE_COW {
+        return -1;
+    }
+
+    let pa = pte2pa(*pte);
+    let flags = (pte_flags(*pte) | PTE_W) & !PTE_COW;
+    // The other processes that shared the page have copied it or exited, so it's ours.
+    if kalloc::refcount(pa) == 1 {
+        *pte = pa2pte(pa) | flags;
+        return 0;
+    }
+
+    let mem = kalloc();
+    if mem.is_null() {
+        return -1;
+    }
+    memmove(mem, pa as *const c_void, PGSIZE as c_uint);
+    *pte = pa2pte(mem as u64) | flags;
+    kfree(pa as *mut c_void);
+    0
+}
@@ -1,51 +1,47 @@
 #![no_std]
 
-use core::fmt::Write;
-use xv6_defs::c_types::*;
-use xv6_ulib::Args;
+use xv6_ulib::io::{self, Read, Write};
+use xv6_ulib::{eprintln, Args, File};
 
-fn cat(fd: i32, buf: &[u8]) {
+fn cat(input: &mut impl Read) -> Result<(), ()> {
+    let mut buf = [0; 512];
+    let mut out = io::Fd(1);
     loop {
-        let n = match xv6_ulib::read(fd, buf) {
-            Ok(0) => return,
+        let n = match input.read(&mut buf) {
+            Ok(0) => return Ok(()),
             Ok(n) => n,
-            Err(_) => {
-                writeln!(xv6_ulib::stdout(), "cat: read error").unwrap();
-                return;
+            Err(e) => {
+                eprintln!("cat: read error: {}", e);
+                return Err(());
             }
         };
 
-        if xv6_ulib::write(1, &buf[0..n]) != Ok(n) {
-            writeln!(xv6_ulib::stdout(), "cat: write error").unwrap();
-            return;
+        if out.write_all(&buf[0..n]).is_err() {
+            eprintln!("cat: write error");
+            return Err(());
         }
     }
 }
 
 fn run(args: Args) -> i32 {
-    let argc = args.argc();
-    let buf: [u8; 512] = [0; 512];
-    if argc <= 1 {
-        cat(0, &buf);
+    if args.argc() <= 1 {
+        let _ = cat(&mut io::Fd(0));
         return 1;
     }
 
     for arg in args.skip(1) {
-        let fd = match xv6_ulib::open(arg.as_bytes(), 0) {
-            Ok(fd) => fd,
-            Err(_) => {
-                writeln!(xv6_ulib::stdout(), "cat: cannot open {}", arg.as_str()).unwrap();
+        let mut file = match File::open(&arg) {
+            Ok(file) => file,
+            Err(e) => {
+                eprintln!("cat: cannot open {}: {}", arg.chars_as_str(), e);
                 return 1;
             }
         };
-        cat(fd, &buf);
-        xv6_ulib::close(fd).unwrap();
+        if cat(&mut file).is_err() {
+            return 1;
+        }
     }
     0
 }
 
-/// Entry point.
-#[no_mangle]
-pub unsafe extern "C" fn main(argc: c_int, argv: *const *const c_char) -> c_int {
-    xv6_ulib::run_prog(argc, argv, run)
-}
+xv6_ulib::entry!(run);
@@ -0,0 +1,98 @@
+//! Simple grep. Only supports the `^ . * $` operators.
+
+#![no_std]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use xv6_ulib::io::{self, BufRead, BufReader, Read, Write};
+use xv6_ulib::{eprintln, println, Args, File};
+
+/// Prints the lines of `input` that match `pattern`.
+fn grep(pattern: &[u8], input: impl Read) {
+    let mut input = BufReader::new(input);
+    let mut out = io::stdout();
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        match input.read_until(b'\n', &mut line) {
+            Ok(0) | Err(_) => return,
+            Ok(_) => {}
+        }
+        let text = match line.last() {
+            Some(b'\n') => &line[..line.len() - 1],
+            _ => &line[..],
+        };
+        if is_match(pattern, text) {
+            let _ = out.write_all(&line);
+        }
+    }
+}
+
+fn run(args: Args) -> i32 {
+    let mut args = args.skip(1);
+    let pattern = match args.next() {
+        Some(pattern) => pattern,
+        None => {
+            eprintln!("usage: grep pattern [file ...]");
+            return 1;
+        }
+    };
+    let pattern = pattern.chars_as_bytes();
+
+    let mut files = args.peekable();
+    if files.peek().is_none() {
+        grep(pattern, io::Fd(0));
+        return 0;
+    }
+    for path in files {
+        match File::open(&path) {
+            Ok(file) => grep(pattern, file),
+            Err(_) => {
+                println!("grep: cannot open {}", path.chars_as_str());
+                return 1;
+            }
+        }
+    }
+    0
+}
+
+xv6_ulib::entry!(run);
+
+// Regexp matcher from Kernighan & Pike, The Practice of Programming, Chapter 9.
+
+/// Returns whether `re` matches anywhere in `text`.
+fn is_match(re: &[u8], text: &[u8]) -> bool {
+    if let Some((b'^', re)) = re.split_first() {
+        return match_here(re, text);
+    }
+    // Must look at the empty string at the end too.
+    (0..=text.len()).any(|i| match_here(re, &text[i..]))
+}
+
+/// Returns whether `re` matches at the beginning of `text`.
+fn match_here(re: &[u8], text: &[u8]) -> bool {
+    match re {
+        [] => true,
+        [c, b'*', rest @ ..] => match_star(*c, rest, text),
+        [b'$'] => text.is_empty(),
+        [c, rest @ ..] => match text.split_first() {
+            Some((t, text)) if *c == b'.' || c == t => match_here(rest, text),
+            _ => false,
+        },
+    }
+}
+
+/// Returns whether `c*` followed by `re` matches at the beginning of `text`.
+fn match_star(c: u8, re: &[u8], mut text: &[u8]) -> bool {
+    // A * matches zero or more instances.
+    loop {
+        if match_here(re, text) {
+            return true;
+        }
+        match text.split_first() {
+            Some((&t, rest)) if t == c || c == b'.' => text = rest,
+            _ => return false,
+        }
+    }
+}
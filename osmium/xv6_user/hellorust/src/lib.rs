@@ -1,11 +1,10 @@
 #![no_std]
 
-use core::fmt::Write;
-use xv6_ulib::c_user::*;
+use xv6_ulib::{println, Args};
 
-/// Entry point.
-#[no_mangle]
-pub extern "C" fn main() -> i32 {
-    writeln!(xv6_ulib::stdout(), "Hello World").unwrap();
-    unsafe { exit(0) }
+fn run(_args: Args) -> i32 {
+    println!("Hello World");
+    0
 }
+
+xv6_ulib::entry!(run);
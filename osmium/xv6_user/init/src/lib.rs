@@ -0,0 +1,194 @@
+//! init: The initial user-level program.
+//!
+//! Sets up the console as file descriptors 0, 1 and 2, starts the programs listed in
+//! `/etc/inittab`, and then reaps processes forever, restarting the ones marked `respawn`. Orphaned
+//! processes are given to init by the kernel, so reaping them here keeps them from staying zombies.
+//!
+//! Each line of `/etc/inittab` has the form `action:program args...`, where the action is one of
+//!
+//! - `once`: start the program at boot.
+//! - `wait`: start the program at boot and wait for it to exit before going on to the next line.
+//! - `respawn`: start the program at boot and again whenever it exits. A program that exits right
+//!   after starting is restarted after a delay, and given up on after it has done so
+//!   `MAX_QUICK_EXITS` times in a row, so a program that can't run doesn't flood the console.
+//!
+//! Empty lines and lines starting with `#` are ignored. Without an inittab, init respawns `sh`,
+//! like the C version did.
+
+#![no_std]
+
+extern crate alloc;
+
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use xv6_defs::fcntl::O_RDWR;
+use xv6_ulib::io::{BufRead, BufReader};
+use xv6_ulib::process::{self, Command};
+use xv6_ulib::{cstr, println, syscall, Args, Error, File};
+
+/// Major device number of the console, from `kernel/file.h`.
+const CONSOLE: i16 = 1;
+
+/// Ticks to sleep when there are no children to wait for.
+const IDLE_TICKS: i32 = 100;
+
+/// A `respawn` program that exits less than this many ticks after starting exited quickly.
+const QUICK_EXIT_TICKS: i32 = 10;
+/// Ticks to wait before restarting a program that exited quickly.
+const RESPAWN_DELAY: i32 = 10;
+/// Number of quick exits in a row after which a program isn't restarted.
+const MAX_QUICK_EXITS: u32 = 5;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Action {
+    Once,
+    Wait,
+    Respawn,
+}
+
+/// A program to start, from one line of the inittab.
+struct Entry {
+    action: Action,
+    argv: Vec<String>,
+    /// PID of the running program.
+    pid: Option<i32>,
+    /// Uptime when the program was last started.
+    started: i32,
+    /// Number of times in a row the program exited quickly.
+    quick_exits: u32,
+}
+
+impl Entry {
+    fn new(action: Action, argv: Vec<String>) -> Entry {
+        Entry {
+            action,
+            argv,
+            pid: None,
+            started: 0,
+            quick_exits: 0,
+        }
+    }
+
+    /// Starts the program. Exits init if it can't fork, as there is nothing else it can do.
+    fn start(&mut self) {
+        println!("init: starting {}", self.argv[0]);
+        self.started = syscall::uptime();
+        let spawned = Command::new(&self.argv[0])
+            .args(self.argv[1..].iter().map(String::as_str))
+            .spawn();
+        match spawned {
+            Ok(child) => self.pid = Some(child.id()),
+            Err(Error::InvalidArgument) | Err(Error::ArgListTooLong) => {
+                println!("init: bad command for {}", self.argv[0]);
+            }
+            Err(_) => {
+                println!("init: fork failed");
+                syscall::exit(1);
+            }
+        }
+    }
+}
+
+/// Reads `/etc/inittab`. Returns the default of respawning `sh` if it doesn't exist.
+fn read_inittab() -> Vec<Entry> {
+    let file = match File::open(&cstr!("/etc/inittab")) {
+        Ok(file) => file,
+        Err(_) => return vec![Entry::new(Action::Respawn, vec!["sh".to_string()])],
+    };
+
+    let mut entries = Vec::new();
+    for (n, line) in BufReader::new(file).lines().enumerate() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (action, command) = match line.find(':') {
+            Some(i) => (&line[..i], &line[i + 1..]),
+            None => ("", ""),
+        };
+        let action = match action.trim() {
+            "once" => Action::Once,
+            "wait" => Action::Wait,
+            "respawn" => Action::Respawn,
+            _ => {
+                println!("init: /etc/inittab:{}: bad line", n + 1);
+                continue;
+            }
+        };
+        let argv: Vec<String> = command.split_whitespace().map(String::from).collect();
+        if argv.is_empty() {
+            println!("init: /etc/inittab:{}: missing program", n + 1);
+            continue;
+        }
+        entries.push(Entry::new(action, argv));
+    }
+    entries
+}
+
+/// Handles the exit of process `pid`, restarting it if it was a `respawn` entry.
+fn reaped(entries: &mut [Entry], pid: i32) {
+    if let Some(entry) = entries.iter_mut().find(|e| e.pid == Some(pid)) {
+        entry.pid = None;
+        if entry.action != Action::Respawn {
+            return;
+        }
+        if syscall::uptime() - entry.started >= QUICK_EXIT_TICKS {
+            entry.quick_exits = 0;
+        } else {
+            entry.quick_exits += 1;
+            if entry.quick_exits >= MAX_QUICK_EXITS {
+                println!("init: {} keeps exiting, not restarting it", entry.argv[0]);
+                return;
+            }
+            let _ = syscall::sleep(RESPAWN_DELAY);
+        }
+        entry.start();
+    }
+    // Otherwise it was a parentless process; do nothing.
+}
+
+fn run(_args: Args) -> i32 {
+    if syscall::open(&cstr!("console"), O_RDWR).is_err() {
+        let _ = syscall::mknod(&cstr!("console"), CONSOLE, 1);
+        let _ = syscall::open(&cstr!("console"), O_RDWR);
+    }
+    let _ = syscall::dup(0); // stdout
+    let _ = syscall::dup(0); // stderr
+
+    let mut entries = read_inittab();
+    for i in 0..entries.len() {
+        entries[i].start();
+        if entries[i].action != Action::Wait {
+            continue;
+        }
+        while let Some(pid) = entries[i].pid {
+            match process::wait() {
+                Ok((wpid, _)) if wpid == pid => entries[i].pid = None,
+                Ok((wpid, _)) => reaped(&mut entries, wpid),
+                Err(_) => break,
+            }
+        }
+    }
+
+    loop {
+        // This returns if a program started by init exits, or if a parentless process exits.
+        match process::wait() {
+            Ok((pid, _)) => reaped(&mut entries, pid),
+            // No children right now. Orphans may still be handed to init later.
+            Err(Error::NoChildren) => {
+                let _ = syscall::sleep(IDLE_TICKS);
+            }
+            Err(_) => {
+                println!("init: wait returned an error");
+                syscall::exit(1);
+            }
+        }
+    }
+}
+
+xv6_ulib::entry!(run);
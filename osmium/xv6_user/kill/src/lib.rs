@@ -0,0 +1,26 @@
+#![no_std]
+
+use xv6_ulib::argparse::parse_int;
+use xv6_ulib::{eprintln, kill, Args};
+
+fn run(args: Args) -> i32 {
+    if args.argc() < 2 {
+        eprintln!("usage: kill pid...");
+        return 1;
+    }
+    for arg in args.skip(1) {
+        let pid = match parse_int::<i32>(arg.chars_as_str()) {
+            Ok(pid) => pid,
+            Err(e) => {
+                eprintln!("kill: {}", e);
+                continue;
+            }
+        };
+        if let Err(e) = kill(pid) {
+            eprintln!("kill: {}: {}", pid, e);
+        }
+    }
+    0
+}
+
+xv6_ulib::entry!(run);
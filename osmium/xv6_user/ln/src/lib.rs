@@ -0,0 +1,21 @@
+#![no_std]
+
+use xv6_ulib::{eprintln, link, Args};
+
+fn run(args: Args) -> i32 {
+    if args.argc() != 3 {
+        eprintln!("Usage: ln old new");
+        return 1;
+    }
+    let mut args = args.skip(1);
+    let (old, new) = match (args.next(), args.next()) {
+        (Some(old), Some(new)) => (old, new),
+        _ => return 1,
+    };
+    if link(&old, &new).is_err() {
+        eprintln!("link {} {}: failed", old.chars_as_str(), new.chars_as_str());
+    }
+    0
+}
+
+xv6_ulib::entry!(run);
@@ -0,0 +1,80 @@
+#![no_std]
+
+use xv6_defs::fs::DIRSIZ;
+use xv6_ulib::stat::Stat;
+use xv6_ulib::{cstr, eprintln, println, read_dir, Args, CStr, File, FileType};
+
+/// Size of the path buffer in the C version, which limits the directories it can list.
+const BUFSIZE: usize = 512;
+
+/// Prints one line of the listing. The name is the last component of `path`, padded with blanks
+/// to `DIRSIZ` characters.
+fn print_entry(path: &str, st: &Stat) {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    println!(
+        "{:<width$} {} {} {}",
+        name,
+        st.file_type().to_raw(),
+        st.ino,
+        st.size,
+        width = DIRSIZ
+    );
+}
+
+fn ls(path: &CStr) {
+    let name = path.chars_as_str();
+    let st = match File::open(path) {
+        Ok(file) => match file.metadata() {
+            Ok(st) => st,
+            Err(_) => {
+                eprintln!("ls: cannot stat {}", name);
+                return;
+            }
+        },
+        Err(_) => {
+            eprintln!("ls: cannot open {}", name);
+            return;
+        }
+    };
+
+    match st.file_type() {
+        FileType::File => print_entry(name, &st),
+        FileType::Dir => {
+            if name.len() + 1 + DIRSIZ + 1 > BUFSIZE {
+                println!("ls: path too long");
+                return;
+            }
+            let entries = match read_dir(path) {
+                Ok(entries) => entries,
+                Err(_) => {
+                    eprintln!("ls: cannot open {}", name);
+                    return;
+                }
+            };
+            for entry in entries {
+                let entry = match entry {
+                    Ok(entry) => entry,
+                    Err(_) => break,
+                };
+                match entry.metadata() {
+                    Ok(st) => print_entry(entry.path(), &st),
+                    Err(_) => println!("ls: cannot stat {}", entry.path()),
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn run(args: Args) -> i32 {
+    if args.argc() < 2 {
+        ls(&cstr!("."));
+        return 0;
+    }
+    for arg in args.skip(1) {
+        ls(&arg);
+    }
+    0
+}
+
+xv6_ulib::entry!(run);
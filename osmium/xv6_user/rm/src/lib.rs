@@ -0,0 +1,19 @@
+#![no_std]
+
+use xv6_ulib::{eprintln, unlink, Args};
+
+fn run(args: Args) -> i32 {
+    if args.argc() < 2 {
+        eprintln!("Usage: rm files...");
+        return 1;
+    }
+    for path in args.skip(1) {
+        if unlink(&path).is_err() {
+            eprintln!("rm: {} failed to delete", path.chars_as_str());
+            break;
+        }
+    }
+    0
+}
+
+xv6_ulib::entry!(run);
@@ -0,0 +1,442 @@
+//! Tests for `xv6_ulib` and the system calls implemented in Rust in the kernel. Run with no
+//! arguments to run every test, or with the names of the tests to run.
+//!
+//! Each test runs in its own process (see `xv6_test`). Files are created in the current
+//! directory with names starting with `rt_`, and removed again by the test that made them.
+
+#![no_std]
+
+extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use xv6_defs::fcntl::*;
+use xv6_ulib::fs::{self, OpenOptions};
+use xv6_ulib::io::{BufRead, BufReader, Read, Write};
+use xv6_ulib::process::{self, Command, ForkResult};
+use xv6_ulib::*;
+
+const PGSIZE: i32 = 4096;
+
+/// Forks a child that runs `f` and exits with status 0. Returns the child's PID in the parent.
+fn spawn(f: impl FnOnce()) -> i32 {
+    match process::fork().unwrap() {
+        ForkResult::Child => {
+            f();
+            exit(0)
+        }
+        ForkResult::Parent(pid) => pid,
+    }
+}
+
+/// Writes `data` to a new file at `path`.
+fn write_file(path: &CStr, data: &[u8]) {
+    File::create(path).unwrap().write_all(data).unwrap();
+}
+
+/// Returns the contents of the file at `path`.
+fn read_file(path: &CStr) -> Vec<u8> {
+    let mut data = Vec::new();
+    File::open(path).unwrap().read_to_end(&mut data).unwrap();
+    data
+}
+
+// Processes: fork, exit, wait, getpid, kill, exec.
+
+fn fork_exit_wait() {
+    let pid = spawn(|| exit(7));
+    assert_eq!(process::wait().unwrap(), (pid, 7));
+}
+
+fn wait_no_children() {
+    assert_eq!(process::wait(), Err(Error::NoChildren));
+}
+
+fn getpid_in_child() {
+    let (mut r, mut w) = fs::pipe().unwrap();
+    let pid = spawn(move || {
+        w.write_all(&getpid().to_le_bytes()).unwrap();
+    });
+    let mut buf = [0; 4];
+    r.read_exact(&mut buf).unwrap();
+    assert_eq!(i32::from_le_bytes(buf), pid);
+    assert_ne!(pid, getpid());
+    assert_eq!(process::wait().unwrap(), (pid, 0));
+}
+
+fn kill_child() {
+    let pid = spawn(|| loop {
+        let _ = sleep(1);
+    });
+    kill(pid).unwrap();
+    assert_eq!(process::wait().unwrap(), (pid, -1));
+}
+
+fn kill_missing() {
+    assert_eq!(kill(100_000), Err(Error::NoSuchProcess));
+}
+
+fn panic_status() {
+    let pid = spawn(|| panic!("expected panic"));
+    assert_eq!(process::wait().unwrap(), (pid, PANIC_STATUS));
+}
+
+fn exec_output() {
+    let output = Command::new("echo")
+        .args(["hello", "world"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status, 0);
+    assert_eq!(output.stdout, b"hello world\n");
+}
+
+fn exec_missing() {
+    let path = cstr!("rt_nonexistent");
+    assert_eq!(exec(&path, &[cstr!("rt_nonexistent")]), Error::NotFound);
+}
+
+fn exec_too_many_args() {
+    let args: Vec<CStr> = (0..xv6_defs::param::MAXARG)
+        .map(|_| cstr!("echo"))
+        .collect();
+    assert_eq!(exec(&cstr!("echo"), &args), Error::ArgListTooLong);
+}
+
+fn command_pipes() {
+    let mut child = Command::new("wc")
+        .stdin(process::Stdio::Piped)
+        .stdout(process::Stdio::Piped)
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"one two\nthree\n")
+        .unwrap();
+    let mut out = String::new();
+    child
+        .stdout
+        .take()
+        .unwrap()
+        .read_to_string(&mut out)
+        .unwrap();
+    assert_eq!(child.wait().unwrap(), 0);
+    assert_eq!(out, "2 3 14 \n");
+}
+
+fn sh_reader_exits_early() {
+    // echo exits without reading, so cat's writes must fail rather than block once the pipe
+    // fills up, which README is large enough to do.
+    let path = cstr!("rt_sh");
+    write_file(&path, b"cat /README | echo hi\n");
+    let output = Command::new("sh").arg("rt_sh").output().unwrap();
+    unlink(&path).unwrap();
+    assert_eq!(output.status, 0);
+    assert_eq!(output.stdout, b"hi\n");
+}
+
+// File descriptors: pipe, read, write, close, dup.
+
+fn pipe_roundtrip() {
+    let (mut r, mut w) = fs::pipe().unwrap();
+    w.write_all(b"ping").unwrap();
+    drop(w);
+    let mut data = Vec::new();
+    r.read_to_end(&mut data).unwrap();
+    assert_eq!(data, b"ping");
+}
+
+fn pipe_closed_reader() {
+    let (r, mut w) = fs::pipe().unwrap();
+    drop(r);
+    assert_eq!(w.write(b"x"), Err(Error::BrokenPipe));
+}
+
+fn close_bad_fd() {
+    assert_eq!(
+        close(xv6_defs::param::NOFILE as i32),
+        Err(Error::BadFileDescriptor)
+    );
+    assert_eq!(dup(-1), Err(Error::BadFileDescriptor));
+}
+
+fn dup_shares_offset() {
+    let path = cstr!("rt_dup");
+    let mut a = File::create(&path).unwrap();
+    let mut b = a.try_clone().unwrap();
+    a.write_all(b"ab").unwrap();
+    b.write_all(b"cd").unwrap();
+    drop((a, b));
+    assert_eq!(read_file(&path), b"abcd");
+    unlink(&path).unwrap();
+}
+
+// Files and directories: open, fstat, stat, link, unlink, mkdir, chdir, mknod.
+
+fn file_roundtrip() {
+    let path = cstr!("rt_file");
+    write_file(&path, b"hello");
+    assert_eq!(read_file(&path), b"hello");
+    let st = stat(&path).unwrap();
+    assert!(st.is_file());
+    assert_eq!(st.size, 5);
+    assert_eq!(st.nlink, 1);
+    unlink(&path).unwrap();
+    assert_eq!(File::open(&path).err(), Some(Error::NotFound));
+}
+
+fn open_errors() {
+    assert_eq!(
+        File::open(&cstr!("rt_missing")).err(),
+        Some(Error::NotFound)
+    );
+    assert_eq!(open(&cstr!("/"), O_WRONLY), Err(Error::IsADirectory));
+    assert_eq!(
+        open(&cstr!("rt_missing/file"), O_CREATE | O_RDWR),
+        Err(Error::NotFound)
+    );
+}
+
+fn open_truncate_append() {
+    let path = cstr!("rt_append");
+    write_file(&path, b"12345");
+    let opts = || {
+        let mut opts = OpenOptions::new();
+        opts.write(true);
+        opts
+    };
+    opts().open(&path).unwrap().write_all(b"ab").unwrap();
+    assert_eq!(read_file(&path), b"ab345");
+    opts()
+        .append(true)
+        .open(&path)
+        .unwrap()
+        .write_all(b"xy")
+        .unwrap();
+    assert_eq!(read_file(&path), b"ab345xy");
+    opts()
+        .truncate(true)
+        .open(&path)
+        .unwrap()
+        .write_all(b"z")
+        .unwrap();
+    assert_eq!(read_file(&path), b"z");
+    unlink(&path).unwrap();
+}
+
+fn fstat_fields() {
+    let path = cstr!("rt_fstat");
+    let file = File::create(&path).unwrap();
+    let st = file.metadata().unwrap();
+    assert_eq!(st.file_type(), FileType::File);
+    assert_eq!(st.size, 0);
+    assert_eq!(st.ino, stat(&path).unwrap().ino);
+    unlink(&path).unwrap();
+}
+
+fn link_unlink() {
+    let (a, b) = (cstr!("rt_link_a"), cstr!("rt_link_b"));
+    write_file(&a, b"x");
+    link(&a, &b).unwrap();
+    assert_eq!(stat(&a).unwrap().nlink, 2);
+    assert_eq!(stat(&a).unwrap().ino, stat(&b).unwrap().ino);
+    assert_eq!(link(&a, &b), Err(Error::AlreadyExists));
+    unlink(&a).unwrap();
+    assert_eq!(stat(&b).unwrap().nlink, 1);
+    assert_eq!(read_file(&b), b"x");
+    unlink(&b).unwrap();
+    assert_eq!(unlink(&b), Err(Error::NotFound));
+}
+
+fn mkdir_chdir() {
+    let dir = cstr!("rt_dir");
+    mkdir(&dir).unwrap();
+    assert!(stat(&dir).unwrap().is_dir());
+    assert_eq!(mkdir(&dir), Err(Error::AlreadyExists));
+    chdir(&dir).unwrap();
+    write_file(&cstr!("inner"), b"");
+    chdir(&cstr!("..")).unwrap();
+    assert!(stat(&cstr!("rt_dir/inner")).unwrap().is_file());
+    assert_eq!(unlink(&dir), Err(Error::DirectoryNotEmpty));
+    assert_eq!(chdir(&cstr!("rt_dir/inner")), Err(Error::NotADirectory));
+    unlink(&cstr!("rt_dir/inner")).unwrap();
+    unlink(&dir).unwrap();
+}
+
+fn mknod_device() {
+    let path = cstr!("rt_console");
+    mknod(&path, 1, 1).unwrap();
+    assert!(stat(&path).unwrap().is_device());
+    unlink(&path).unwrap();
+}
+
+fn read_dir_entries() {
+    let dir = cstr!("rt_ls");
+    mkdir(&dir).unwrap();
+    write_file(&cstr!("rt_ls/a"), b"1");
+    mkdir(&cstr!("rt_ls/b")).unwrap();
+    let mut names = Vec::new();
+    for entry in read_dir(&dir).unwrap() {
+        let entry = entry.unwrap();
+        let typ = entry.file_type().unwrap();
+        names.push((String::from(entry.name()), typ));
+    }
+    names.sort_by(|a, b| a.0.cmp(&b.0));
+    assert_eq!(
+        names,
+        vec![
+            (String::from("."), FileType::Dir),
+            (String::from(".."), FileType::Dir),
+            (String::from("a"), FileType::File),
+            (String::from("b"), FileType::Dir),
+        ]
+    );
+    assert_eq!(
+        read_dir(&cstr!("rt_ls/a")).err(),
+        Some(Error::NotADirectory)
+    );
+    unlink(&cstr!("rt_ls/a")).unwrap();
+    unlink(&cstr!("rt_ls/b")).unwrap();
+    unlink(&dir).unwrap();
+}
+
+// Library types.
+
+fn cstr_and_paths() {
+    assert!(CStr::try_new("ok\0").is_ok());
+    assert_eq!(
+        CStr::try_new("no terminator").err(),
+        Some(Error::InvalidArgument)
+    );
+    assert_eq!(
+        CStr::try_new("in\0side\0").err(),
+        Some(Error::InvalidArgument)
+    );
+
+    let mut path = PathBuf::try_from_str("/usr").unwrap();
+    path.push("bin/").unwrap();
+    path.push("ls").unwrap();
+    assert_eq!(path.as_str(), "/usr/bin/ls");
+    assert_eq!(path.as_c_str().as_bytes(), b"/usr/bin/ls\0");
+    assert_eq!(path.file_name(), Some("ls"));
+    assert_eq!(path.parent(), Some("/usr/bin"));
+    assert_eq!(path.components().collect::<Vec<_>>(), ["usr", "bin", "ls"]);
+    assert!(path.pop() && path.pop() && path.pop());
+    assert_eq!(path.as_str(), "/");
+    assert!(!path.pop());
+
+    let long = "x".repeat(xv6_defs::param::MAXPATH);
+    assert_eq!(PathBuf::try_from_str(&long).err(), Some(Error::NameTooLong));
+}
+
+fn buffered_lines() {
+    let path = cstr!("rt_lines");
+    write_file(&path, b"first\nsecond\n\nlast");
+    let lines: Vec<String> = BufReader::new(File::open(&path).unwrap())
+        .lines()
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(lines, ["first", "second", "", "last"]);
+    unlink(&path).unwrap();
+}
+
+fn heap_alloc() {
+    let mut v: Vec<u64> = (0..20_000).collect();
+    let b = Box::new([7u8; 1000]);
+    assert_eq!(v.iter().sum::<u64>(), 19_999 * 20_000 / 2);
+    v.truncate(10);
+    v.shrink_to_fit();
+    let s = "abc".repeat(5_000);
+    assert_eq!(s.len(), 15_000);
+    assert_eq!(b[999], 7);
+}
+
+// The system calls implemented in Rust in the kernel: sbrk, sleep, kill, nfree.
+
+fn sbrk_grow_shrink() {
+    unsafe {
+        let start = sbrk(0).unwrap();
+        let len = 4 * PGSIZE;
+        assert_eq!(sbrk(len).unwrap(), start);
+        let mem = core::slice::from_raw_parts_mut(start, len as usize);
+        mem.fill(0xaa);
+        assert_eq!(sbrk(0).unwrap(), start.add(len as usize));
+        assert_eq!(sbrk(-len).unwrap(), start.add(len as usize));
+        assert_eq!(sbrk(0).unwrap(), start);
+    }
+}
+
+fn sleep_ticks() {
+    let start = uptime();
+    sleep(5).unwrap();
+    assert!(uptime() - start >= 5);
+}
+
+fn sleep_killed() {
+    let pid = spawn(|| {
+        let _ = sleep(1000);
+        // Only reached if sleep returned without the process being killed.
+        exit(3)
+    });
+    sleep(2).unwrap();
+    kill(pid).unwrap();
+    assert_eq!(process::wait().unwrap(), (pid, -1));
+}
+
+fn nfree_tracks_sbrk() {
+    let pages = 8;
+    let before = nfree();
+    unsafe {
+        let start = sbrk(pages * PGSIZE).unwrap();
+        // Touch every page so they are allocated even if sbrk is lazy.
+        for i in 0..pages {
+            start.add((i * PGSIZE) as usize).write_volatile(1);
+        }
+        let during = nfree();
+        assert!(during + pages as usize <= before);
+        sbrk(-pages * PGSIZE).unwrap();
+        assert!(nfree() >= during + pages as usize);
+    }
+}
+
+fn ntas_reset() {
+    assert_eq!(ntas(false), 0);
+}
+
+xv6_test::tests!(
+    "rusttests",
+    fork_exit_wait,
+    wait_no_children,
+    getpid_in_child,
+    kill_child,
+    kill_missing,
+    panic_status,
+    exec_output,
+    exec_missing,
+    exec_too_many_args,
+    command_pipes,
+    sh_reader_exits_early,
+    pipe_roundtrip,
+    pipe_closed_reader,
+    close_bad_fd,
+    dup_shares_offset,
+    file_roundtrip,
+    open_errors,
+    open_truncate_append,
+    fstat_fields,
+    link_unlink,
+    mkdir_chdir,
+    mknod_device,
+    read_dir_entries,
+    cstr_and_paths,
+    buffered_lines,
+    heap_alloc,
+    sbrk_grow_shrink,
+    sleep_ticks,
+    sleep_killed,
+    nfree_tracks_sbrk,
+    ntas_reset,
+);
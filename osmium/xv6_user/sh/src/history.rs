@@ -0,0 +1,60 @@
+//! History of the lines entered in the shell.
+//!
+//! The last `HISTSIZE` lines are kept and numbered from 1 in the order they were entered. A line
+//! consisting of `!!` repeats the previous line, and `!N` repeats line `N`.
+
+use alloc::collections::VecDeque;
+use alloc::string::String;
+
+/// Number of lines kept.
+const HISTSIZE: usize = 32;
+
+pub struct History {
+    lines: VecDeque<String>,
+    /// Number of the oldest line kept.
+    first: usize,
+}
+
+impl History {
+    pub fn new() -> History {
+        History {
+            lines: VecDeque::with_capacity(HISTSIZE),
+            first: 1,
+        }
+    }
+
+    /// Records `line`, dropping the oldest line if the history is full.
+    pub fn push(&mut self, line: &str) {
+        if self.lines.len() == HISTSIZE {
+            self.lines.pop_front();
+            self.first += 1;
+        }
+        self.lines.push_back(String::from(line));
+    }
+
+    /// Returns the kept lines with their numbers.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &str)> {
+        (self.first..).zip(self.lines.iter().map(String::as_str))
+    }
+
+    /// If `line` is a history reference, returns the line it refers to. Returns `Err` with a
+    /// message for a reference to a line that isn't kept, and `Ok(None)` for other lines.
+    pub fn expand(&self, line: &str) -> Result<Option<String>, &'static str> {
+        let reference = match line.strip_prefix('!') {
+            Some(reference) => reference,
+            None => return Ok(None),
+        };
+        let index = if reference == "!" {
+            self.lines.len().checked_sub(1)
+        } else {
+            match reference.parse::<usize>() {
+                Ok(n) => n.checked_sub(self.first),
+                Err(_) => return Ok(None),
+            }
+        };
+        match index.and_then(|i| self.lines.get(i)) {
+            Some(line) => Ok(Some(line.clone())),
+            None => Err("event not found"),
+        }
+    }
+}
@@ -0,0 +1,94 @@
+//! The xv6 shell.
+//!
+//! Reads command lines from standard input, or from the file named by its argument, and runs
+//! them. See `parse` for the syntax. The builtins are `cd`, `exit [status]`, `history`, `set`
+//! (list variables) and `unset NAME...`.
+
+#![no_std]
+
+extern crate alloc;
+
+mod history;
+mod parse;
+mod run;
+
+use alloc::string::String;
+use run::Shell;
+use xv6_defs::fcntl::O_RDWR;
+use xv6_ulib::io::{BufRead, BufReader, BufferMode};
+use xv6_ulib::{cstr, eprint, eprintln, stdin, syscall, Args, File};
+
+/// Runs one line of input.
+fn run_line(shell: &mut Shell, line: &str) {
+    let line = line.trim();
+    if line.is_empty() {
+        return;
+    }
+    let line = match shell.history.expand(line) {
+        Ok(Some(expanded)) => {
+            eprintln!("{}", expanded);
+            expanded
+        }
+        Ok(None) => String::from(line),
+        Err(e) => {
+            eprintln!("sh: {}: {}", line, e);
+            shell.status = 1;
+            return;
+        }
+    };
+    shell.history.push(&line);
+    match parse::parse(&line) {
+        Ok(list) => {
+            shell.run_list(&list);
+        }
+        Err(e) => {
+            eprintln!("sh: {}", e);
+            shell.status = 2;
+        }
+    }
+}
+
+fn run(mut args: Args) -> i32 {
+    // Ensure that three file descriptors are open.
+    while let Ok(fd) = syscall::open(&cstr!("console"), O_RDWR) {
+        if fd >= 3 {
+            let _ = syscall::close(fd);
+            break;
+        }
+    }
+
+    let mut shell = Shell::new();
+    let mut line = String::new();
+    if let Some(path) = args.nth(1) {
+        let mut script = match File::open(&path) {
+            Ok(file) => BufReader::new(file),
+            Err(e) => {
+                eprintln!("sh: cannot open {}: {}", path.chars_as_str(), e);
+                return 1;
+            }
+        };
+        while let Ok(n) = script.read_line(&mut line) {
+            if n == 0 {
+                break;
+            }
+            run_line(&mut shell, &line);
+            line.clear();
+        }
+        return shell.status;
+    }
+
+    // Read one byte at a time so commands that read standard input see everything after the
+    // current line.
+    stdin().set_buffer_mode(BufferMode::Unbuffered);
+    loop {
+        eprint!("$ ");
+        match stdin().read_line(&mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => run_line(&mut shell, &line),
+        }
+        line.clear();
+    }
+    0
+}
+
+xv6_ulib::entry!(run);
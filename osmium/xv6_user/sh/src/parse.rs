@@ -0,0 +1,444 @@
+//! Parsing of command lines.
+//!
+//! The grammar extends the one `user/sh.c` accepts:
+//!
+//! ```text
+//! list     := and_or (('&' | ';') and_or)* ('&' | ';')?
+//! and_or   := pipeline (('&&' | '||') pipeline)*
+//! pipeline := command ('|' command)*
+//! command  := '(' list ')' redir* | (word | redir)+
+//! redir    := ('<' | '>' | '>>') word
+//! ```
+//!
+//! A word is a run of characters other than whitespace and `<|>&;()`. Inside a word, `'...'`
+//! quotes literally, `"..."` quotes but still expands variables, `\` escapes the next character,
+//! and `$?` and `$NAME` are replaced by the last exit status and the variable `NAME` when the
+//! command runs. A command consisting of a single `NAME=value` word sets a variable. `#` at the
+//! start of a word begins a comment.
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// Part of a word, expanded when the command runs.
+#[derive(Debug)]
+pub enum WordPart {
+    Literal(String),
+    /// `$NAME`
+    Var(String),
+    /// `$?`
+    Status,
+}
+
+/// A command argument or redirection target.
+#[derive(Debug, Default)]
+pub struct Word {
+    pub parts: Vec<WordPart>,
+}
+
+impl Word {
+    fn push_char(&mut self, c: char) {
+        match self.parts.last_mut() {
+            Some(WordPart::Literal(s)) => s.push(c),
+            _ => {
+                let mut s = String::new();
+                s.push(c);
+                self.parts.push(WordPart::Literal(s));
+            }
+        }
+    }
+
+    /// If the word has the form `NAME=value`, splits it into the name and the value.
+    fn as_assignment(&mut self) -> Option<(String, Word)> {
+        let first = match self.parts.first() {
+            Some(WordPart::Literal(s)) => s,
+            _ => return None,
+        };
+        let eq = first.find('=')?;
+        if !is_name(&first[..eq]) {
+            return None;
+        }
+        let name = String::from(&first[..eq]);
+        let mut parts = core::mem::take(&mut self.parts);
+        let rest = match parts.remove(0) {
+            WordPart::Literal(s) => String::from(&s[eq + 1..]),
+            _ => unreachable!(),
+        };
+        if !rest.is_empty() {
+            parts.insert(0, WordPart::Literal(rest));
+        }
+        Some((name, Word { parts }))
+    }
+}
+
+/// Returns whether `s` can be used as a variable name.
+pub fn is_name(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RedirKind {
+    /// `<`
+    Input,
+    /// `>`
+    Output,
+    /// `>>`
+    Append,
+}
+
+#[derive(Debug)]
+pub struct Redir {
+    pub kind: RedirKind,
+    pub target: Word,
+}
+
+#[derive(Debug)]
+pub enum Command {
+    /// A program or builtin with its arguments.
+    Simple { argv: Vec<Word>, redirs: Vec<Redir> },
+    /// A parenthesised list, run in a child shell.
+    Subshell { list: Box<List>, redirs: Vec<Redir> },
+    /// `NAME=value`
+    Assign { name: String, value: Word },
+}
+
+/// Commands connected by pipes.
+#[derive(Debug)]
+pub struct Pipeline {
+    pub commands: Vec<Command>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Connector {
+    /// `&&`: run the next pipeline if the previous one succeeded.
+    And,
+    /// `||`: run the next pipeline if the previous one failed.
+    Or,
+}
+
+/// Pipelines connected by `&&` and `||`.
+#[derive(Debug)]
+pub struct AndOr {
+    pub first: Pipeline,
+    pub rest: Vec<(Connector, Pipeline)>,
+}
+
+/// An element of a list, run in the background if it was followed by `&`.
+#[derive(Debug)]
+pub struct Item {
+    pub and_or: AndOr,
+    pub background: bool,
+}
+
+/// Items separated by `;` or `&`.
+#[derive(Debug, Default)]
+pub struct List {
+    pub items: Vec<Item>,
+}
+
+#[derive(Debug)]
+enum Token {
+    Word(Word),
+    Pipe,
+    OrOr,
+    Amp,
+    AndAnd,
+    Semi,
+    LParen,
+    RParen,
+    Less,
+    Great,
+    GreatGreat,
+}
+
+impl Token {
+    fn describe(&self) -> &'static str {
+        match self {
+            Token::Word(_) => "word",
+            Token::Pipe => "|",
+            Token::OrOr => "||",
+            Token::Amp => "&",
+            Token::AndAnd => "&&",
+            Token::Semi => ";",
+            Token::LParen => "(",
+            Token::RParen => ")",
+            Token::Less => "<",
+            Token::Great => ">",
+            Token::GreatGreat => ">>",
+        }
+    }
+}
+
+/// Why a command line couldn't be parsed.
+#[derive(Debug)]
+pub enum ParseError {
+    /// The token wasn't expected where it appeared.
+    Unexpected(&'static str),
+    /// The line ended early, e.g. after `|` or without a closing `)`.
+    UnexpectedEnd,
+    /// A quote wasn't closed.
+    UnterminatedQuote,
+    /// A redirection wasn't followed by a file name.
+    MissingFile,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::Unexpected(tok) => write!(f, "syntax error near '{}'", tok),
+            ParseError::UnexpectedEnd => f.write_str("syntax error: unexpected end of line"),
+            ParseError::UnterminatedQuote => f.write_str("syntax error: unterminated quote"),
+            ParseError::MissingFile => f.write_str("missing file for redirection"),
+        }
+    }
+}
+
+const WHITESPACE: &str = " \t\r\n\x0b";
+const SYMBOLS: &str = "<|>&;()";
+
+/// Splits a line into tokens.
+fn tokenize(line: &str) -> Result<Vec<Token>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut chars = line.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if WHITESPACE.contains(c) {
+            chars.next();
+            continue;
+        }
+        if c == '#' {
+            break;
+        }
+        if SYMBOLS.contains(c) {
+            chars.next();
+            let next = chars.peek().copied();
+            let tok = match (c, next) {
+                ('|', Some('|')) => Token::OrOr,
+                ('&', Some('&')) => Token::AndAnd,
+                ('>', Some('>')) => Token::GreatGreat,
+                ('|', _) => Token::Pipe,
+                ('&', _) => Token::Amp,
+                ('>', _) => Token::Great,
+                (';', _) => Token::Semi,
+                ('(', _) => Token::LParen,
+                (')', _) => Token::RParen,
+                _ => Token::Less,
+            };
+            if let Token::OrOr | Token::AndAnd | Token::GreatGreat = tok {
+                chars.next();
+            }
+            tokens.push(tok);
+            continue;
+        }
+
+        let mut word = Word::default();
+        let mut quote = None;
+        while let Some(&c) = chars.peek() {
+            if quote.is_none() && (WHITESPACE.contains(c) || SYMBOLS.contains(c)) {
+                break;
+            }
+            chars.next();
+            match (quote, c) {
+                (Some('\''), '\'') | (Some('"'), '"') => quote = None,
+                (Some('\''), c) => word.push_char(c),
+                (None, '\'') | (None, '"') => {
+                    quote = Some(c);
+                    // Keep `''` as an empty argument.
+                    if word.parts.is_empty() {
+                        word.parts.push(WordPart::Literal(String::new()));
+                    }
+                }
+                (_, '\\') => {
+                    if let Some(c) = chars.next() {
+                        word.push_char(c);
+                    }
+                }
+                (_, '$') => match chars.peek().copied() {
+                    Some('?') => {
+                        chars.next();
+                        word.parts.push(WordPart::Status);
+                    }
+                    Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+                        let mut name = String::new();
+                        while let Some(&c) = chars.peek() {
+                            if !(c.is_ascii_alphanumeric() || c == '_') {
+                                break;
+                            }
+                            name.push(c);
+                            chars.next();
+                        }
+                        word.parts.push(WordPart::Var(name));
+                    }
+                    _ => word.push_char('$'),
+                },
+                (_, c) => word.push_char(c),
+            }
+        }
+        if quote.is_some() {
+            return Err(ParseError::UnterminatedQuote);
+        }
+        tokens.push(Token::Word(word));
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent parser over the tokens of one line.
+struct Parser {
+    tokens: Vec<Option<Token>>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).and_then(Option::as_ref)
+    }
+
+    fn take(&mut self) -> Option<Token> {
+        let tok = self.tokens.get_mut(self.pos).and_then(Option::take);
+        self.pos += 1;
+        tok
+    }
+
+    fn unexpected(&self) -> ParseError {
+        match self.peek() {
+            Some(tok) => ParseError::Unexpected(tok.describe()),
+            None => ParseError::UnexpectedEnd,
+        }
+    }
+
+    /// Returns whether the next token starts a command.
+    fn at_command(&self) -> bool {
+        matches!(
+            self.peek(),
+            Some(Token::Word(_))
+                | Some(Token::LParen)
+                | Some(Token::Less)
+                | Some(Token::Great)
+                | Some(Token::GreatGreat)
+        )
+    }
+
+    fn list(&mut self) -> Result<List, ParseError> {
+        let mut list = List::default();
+        while self.at_command() {
+            let and_or = self.and_or()?;
+            let background = matches!(self.peek(), Some(Token::Amp));
+            let separated = background || matches!(self.peek(), Some(Token::Semi));
+            if separated {
+                self.take();
+            }
+            list.items.push(Item { and_or, background });
+            if !separated {
+                break;
+            }
+        }
+        Ok(list)
+    }
+
+    fn and_or(&mut self) -> Result<AndOr, ParseError> {
+        let first = self.pipeline()?;
+        let mut rest = Vec::new();
+        loop {
+            let connector = match self.peek() {
+                Some(Token::AndAnd) => Connector::And,
+                Some(Token::OrOr) => Connector::Or,
+                _ => break,
+            };
+            self.take();
+            rest.push((connector, self.pipeline()?));
+        }
+        Ok(AndOr { first, rest })
+    }
+
+    fn pipeline(&mut self) -> Result<Pipeline, ParseError> {
+        let mut commands = Vec::new();
+        commands.push(self.command()?);
+        while let Some(Token::Pipe) = self.peek() {
+            self.take();
+            commands.push(self.command()?);
+        }
+        Ok(Pipeline { commands })
+    }
+
+    fn command(&mut self) -> Result<Command, ParseError> {
+        if let Some(Token::LParen) = self.peek() {
+            self.take();
+            let list = self.list()?;
+            match self.take() {
+                Some(Token::RParen) => {}
+                Some(tok) => return Err(ParseError::Unexpected(tok.describe())),
+                None => return Err(ParseError::UnexpectedEnd),
+            }
+            if list.items.is_empty() {
+                return Err(ParseError::Unexpected(")"));
+            }
+            let mut redirs = Vec::new();
+            while self.redir(&mut redirs)? {}
+            return Ok(Command::Subshell {
+                list: Box::new(list),
+                redirs,
+            });
+        }
+
+        let mut argv = Vec::new();
+        let mut redirs = Vec::new();
+        loop {
+            if self.redir(&mut redirs)? {
+                continue;
+            }
+            match self.peek() {
+                Some(Token::Word(_)) => match self.take() {
+                    Some(Token::Word(word)) => argv.push(word),
+                    _ => unreachable!(),
+                },
+                _ => break,
+            }
+        }
+        if argv.is_empty() && redirs.is_empty() {
+            return Err(self.unexpected());
+        }
+        if argv.len() == 1 && redirs.is_empty() {
+            if let Some((name, value)) = argv[0].as_assignment() {
+                return Ok(Command::Assign { name, value });
+            }
+        }
+        Ok(Command::Simple { argv, redirs })
+    }
+
+    /// Parses a redirection if there is one next. Returns whether one was parsed.
+    fn redir(&mut self, redirs: &mut Vec<Redir>) -> Result<bool, ParseError> {
+        let kind = match self.peek() {
+            Some(Token::Less) => RedirKind::Input,
+            Some(Token::Great) => RedirKind::Output,
+            Some(Token::GreatGreat) => RedirKind::Append,
+            _ => return Ok(false),
+        };
+        self.take();
+        match self.take() {
+            Some(Token::Word(target)) => {
+                redirs.push(Redir { kind, target });
+                Ok(true)
+            }
+            _ => Err(ParseError::MissingFile),
+        }
+    }
+}
+
+/// Parses a command line. An empty line gives an empty list.
+pub fn parse(line: &str) -> Result<List, ParseError> {
+    let tokens = tokenize(line)?;
+    let mut parser = Parser {
+        tokens: tokens.into_iter().map(Some).collect(),
+        pos: 0,
+    };
+    let list = parser.list()?;
+    if parser.peek().is_some() {
+        return Err(parser.unexpected());
+    }
+    Ok(list)
+}
@@ -0,0 +1,392 @@
+//! Running parsed command lines.
+//!
+//! Lists and `&&`/`||` chains are run by the shell itself so it can track the exit status and
+//! variables. Each command in a pipeline runs in its own forked copy of the shell, which sets up
+//! its pipes and redirections and then execs the program, or runs the subshell or builtin itself.
+//! Background items also run in a forked copy of the shell, and are reported when they are reaped.
+
+use crate::history::History;
+use crate::parse::{AndOr, Command, Connector, List, Pipeline, Redir, RedirKind, Word, WordPart};
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use xv6_ulib::io::{self, Write};
+use xv6_ulib::process::{self, ForkResult};
+use xv6_ulib::{eprintln, syscall, CStr, File, PathBuf, Result};
+
+/// Exit status of a command that couldn't be started.
+const FAILED: i32 = 1;
+
+/// State of a running shell.
+pub struct Shell {
+    vars: BTreeMap<String, String>,
+    /// Exit status of the last foreground pipeline, `$?`.
+    pub status: i32,
+    /// PIDs of background items that haven't been reaped.
+    jobs: Vec<i32>,
+    pub history: History,
+}
+
+impl Shell {
+    pub fn new() -> Shell {
+        Shell {
+            vars: BTreeMap::new(),
+            status: 0,
+            jobs: Vec::new(),
+            history: History::new(),
+        }
+    }
+
+    /// Runs each item of `list` in turn. Returns the exit status of the last one run in the
+    /// foreground.
+    pub fn run_list(&mut self, list: &List) -> i32 {
+        for item in &list.items {
+            if item.background {
+                self.spawn_background(&item.and_or);
+            } else {
+                self.status = self.run_and_or(&item.and_or);
+            }
+        }
+        self.status
+    }
+
+    fn run_and_or(&mut self, and_or: &AndOr) -> i32 {
+        let mut status = self.run_pipeline(&and_or.first);
+        for (connector, pipeline) in &and_or.rest {
+            self.status = status;
+            let run = match connector {
+                Connector::And => status == 0,
+                Connector::Or => status != 0,
+            };
+            if run {
+                status = self.run_pipeline(pipeline);
+            }
+        }
+        status
+    }
+
+    /// Runs `and_or` in a forked copy of the shell without waiting for it.
+    fn spawn_background(&mut self, and_or: &AndOr) {
+        match process::fork() {
+            Ok(ForkResult::Child) => {
+                // The jobs belong to the parent shell.
+                self.jobs.clear();
+                let status = self.run_and_or(and_or);
+                process::exit(status)
+            }
+            Ok(ForkResult::Parent(pid)) => {
+                eprintln!("[{}]", pid);
+                self.jobs.push(pid);
+                self.status = 0;
+            }
+            Err(e) => {
+                eprintln!("sh: fork: {}", e);
+                self.status = FAILED;
+            }
+        }
+    }
+
+    /// Runs the commands of `pipeline` connected by pipes and waits for all of them. Returns the
+    /// exit status of the last command.
+    fn run_pipeline(&mut self, pipeline: &Pipeline) -> i32 {
+        // A lone builtin or assignment runs in the shell so it can change the shell's state.
+        if let [command] = pipeline.commands.as_slice() {
+            match command {
+                Command::Assign { name, value } => {
+                    let value = self.expand(value);
+                    self.vars.insert(name.clone(), value);
+                    return 0;
+                }
+                Command::Simple { argv, redirs } => {
+                    let argv = self.expand_all(argv);
+                    if is_builtin(&argv) {
+                        let mut out = match self.open_redirs(redirs) {
+                            Ok((_, Some(file))) => Output::File(file),
+                            Ok((_, None)) => Output::Stdout(io::stdout()),
+                            Err(()) => return FAILED,
+                        };
+                        return self.run_builtin(&argv, &mut out);
+                    }
+                }
+                Command::Subshell { .. } => {}
+            }
+        }
+
+        let mut pids = Vec::new();
+        let mut last_pid = None;
+        let mut prev_read: Option<File> = None;
+        let n = pipeline.commands.len();
+        for (i, command) in pipeline.commands.iter().enumerate() {
+            let stdin = prev_read.take();
+            let (next_read, stdout) = if i + 1 < n {
+                match xv6_ulib::fs::pipe() {
+                    Ok((r, w)) => (Some(r), Some(w)),
+                    Err(e) => {
+                        eprintln!("sh: pipe: {}", e);
+                        break;
+                    }
+                }
+            } else {
+                (None, None)
+            };
+            last_pid = self.spawn(command, stdin, stdout, next_read.as_ref());
+            pids.extend(last_pid);
+            prev_read = next_read;
+        }
+        drop(prev_read);
+
+        let status = self.wait_for(&pids, last_pid);
+        status.unwrap_or(FAILED)
+    }
+
+    /// Starts one command of a pipeline with the given ends of the pipes. Redirections take
+    /// precedence over the pipes. `next_read` is the read end of the command's output pipe, which
+    /// the child closes: if it kept it open, writes to the pipe would block forever once the next
+    /// command exited instead of failing. Returns the PID of the child, or `None` if it couldn't be
+    /// started.
+    fn spawn(
+        &mut self,
+        command: &Command,
+        stdin: Option<File>,
+        stdout: Option<File>,
+        next_read: Option<&File>,
+    ) -> Option<i32> {
+        let redirs = match command {
+            Command::Simple { redirs, .. } | Command::Subshell { redirs, .. } => &redirs[..],
+            Command::Assign { .. } => &[],
+        };
+        let (redir_in, redir_out) = self.open_redirs(redirs).ok()?;
+        let stdin = redir_in.or(stdin);
+        let stdout = redir_out.or(stdout);
+
+        if let Command::Simple { argv, .. } = command {
+            if argv.is_empty() {
+                // Only redirections, which have been done.
+                return None;
+            }
+        }
+
+        match process::fork() {
+            Ok(ForkResult::Child) => {
+                self.jobs.clear();
+                if let Some(file) = next_read {
+                    let _ = syscall::close(file.as_raw_fd());
+                }
+                let redirected = stdin
+                    .map_or(Ok(()), |file| process::redirect(0, file))
+                    .and_then(|_| stdout.map_or(Ok(()), |file| process::redirect(1, file)));
+                if let Err(e) = redirected {
+                    eprintln!("sh: redirect: {}", e);
+                    process::exit(FAILED);
+                }
+                let status = match command {
+                    Command::Subshell { list, .. } => self.run_list(list),
+                    Command::Simple { argv, .. } => {
+                        let argv = self.expand_all(argv);
+                        if is_builtin(&argv) {
+                            self.run_builtin(&argv, &mut Output::Stdout(io::stdout()))
+                        } else {
+                            exec(&argv)
+                        }
+                    }
+                    Command::Assign { .. } => 0,
+                };
+                process::exit(status)
+            }
+            Ok(ForkResult::Parent(pid)) => Some(pid),
+            Err(e) => {
+                eprintln!("sh: fork: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Waits until every process in `pids` has exited, reporting background items reaped in the
+    /// meantime. Returns the exit status of `last`.
+    fn wait_for(&mut self, pids: &[i32], last: Option<i32>) -> Option<i32> {
+        let mut remaining = pids.len();
+        let mut status = None;
+        while remaining > 0 {
+            let (pid, st) = match process::wait() {
+                Ok(result) => result,
+                Err(_) => break,
+            };
+            if pids.contains(&pid) {
+                remaining -= 1;
+                if Some(pid) == last {
+                    status = Some(st);
+                }
+            } else {
+                self.reaped_job(pid, st);
+            }
+        }
+        status
+    }
+
+    /// Reports that the background item `pid` exited with `status`.
+    fn reaped_job(&mut self, pid: i32, status: i32) {
+        if let Some(i) = self.jobs.iter().position(|&job| job == pid) {
+            self.jobs.swap_remove(i);
+            if status == 0 {
+                eprintln!("[{}] done", pid);
+            } else {
+                eprintln!("[{}] exit {}", pid, status);
+            }
+        }
+    }
+
+    /// Opens the files named by `redirs`. Returns the last input and output redirections.
+    fn open_redirs(
+        &self,
+        redirs: &[Redir],
+    ) -> core::result::Result<(Option<File>, Option<File>), ()> {
+        let mut input = None;
+        let mut output = None;
+        for redir in redirs {
+            let name = self.expand(&redir.target);
+            let path = match PathBuf::try_from_str(&name) {
+                Ok(path) => path,
+                Err(e) => {
+                    eprintln!("open {} failed: {}", name, e);
+                    return Err(());
+                }
+            };
+            let mut options = File::options();
+            match redir.kind {
+                RedirKind::Input => options.read(true),
+                RedirKind::Output => options.write(true).create(true).truncate(true),
+                RedirKind::Append => options.write(true).create(true).append(true),
+            };
+            let file = match options.open(&path.as_c_str()) {
+                Ok(file) => file,
+                Err(e) => {
+                    eprintln!("open {} failed: {}", name, e);
+                    return Err(());
+                }
+            };
+            match redir.kind {
+                RedirKind::Input => input = Some(file),
+                RedirKind::Output | RedirKind::Append => output = Some(file),
+            }
+        }
+        Ok((input, output))
+    }
+
+    fn expand(&self, word: &Word) -> String {
+        let mut s = String::new();
+        for part in &word.parts {
+            match part {
+                WordPart::Literal(lit) => s.push_str(lit),
+                WordPart::Var(name) => {
+                    if let Some(value) = self.vars.get(name) {
+                        s.push_str(value);
+                    }
+                }
+                WordPart::Status => s.push_str(&self.status.to_string()),
+            }
+        }
+        s
+    }
+
+    fn expand_all(&self, words: &[Word]) -> Vec<String> {
+        words.iter().map(|word| self.expand(word)).collect()
+    }
+
+    /// Runs the builtin named by `argv[0]`, writing any output to `out`.
+    fn run_builtin(&mut self, argv: &[String], out: &mut Output) -> i32 {
+        let args = &argv[1..];
+        match argv[0].as_str() {
+            "cd" => {
+                let dir = args.first().map_or("/", String::as_str);
+                let result =
+                    PathBuf::try_from_str(dir).and_then(|path| syscall::chdir(&path.as_c_str()));
+                if result.is_err() {
+                    eprintln!("cannot cd {}", dir);
+                    return FAILED;
+                }
+                0
+            }
+            "exit" => {
+                let status = match args.first() {
+                    Some(arg) => match arg.parse() {
+                        Ok(status) => status,
+                        Err(_) => {
+                            eprintln!("exit: invalid status '{}'", arg);
+                            return FAILED;
+                        }
+                    },
+                    None => self.status,
+                };
+                process::exit(status)
+            }
+            "history" => {
+                for (n, line) in self.history.iter() {
+                    let _ = writeln!(out, "{:>4}  {}", n, line);
+                }
+                0
+            }
+            "set" => {
+                for (name, value) in &self.vars {
+                    let _ = writeln!(out, "{}={}", name, value);
+                }
+                0
+            }
+            "unset" => {
+                for name in args {
+                    self.vars.remove(name);
+                }
+                0
+            }
+            _ => unreachable!("not a builtin"),
+        }
+    }
+}
+
+fn is_builtin(argv: &[String]) -> bool {
+    match argv.first() {
+        Some(name) => matches!(name.as_str(), "cd" | "exit" | "history" | "set" | "unset"),
+        None => false,
+    }
+}
+
+/// In a child, replaces the shell with the program `argv[0]`. Only returns, with the exit status
+/// for the child, if the program couldn't be run.
+fn exec(argv: &[String]) -> i32 {
+    let data: Vec<String> = argv.iter().map(|arg| format!("{}\0", arg)).collect();
+    let mut cargv = Vec::with_capacity(data.len());
+    for arg in &data {
+        match CStr::try_new(arg) {
+            Ok(arg) => cargv.push(arg),
+            Err(e) => {
+                eprintln!("sh: {}: {}", argv[0], e);
+                return FAILED;
+            }
+        }
+    }
+    let e = process::exec(&cargv[0], &cargv);
+    eprintln!("exec {} failed: {}", argv[0], e);
+    FAILED
+}
+
+/// Where a builtin run by the shell itself writes its output.
+enum Output {
+    Stdout(io::Stdout),
+    File(File),
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        match self {
+            Output::Stdout(out) => out.write(buf),
+            Output::File(file) => file.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        match self {
+            Output::Stdout(out) => out.flush(),
+            Output::File(file) => file.flush(),
+        }
+    }
+}
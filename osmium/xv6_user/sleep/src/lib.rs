@@ -1,24 +1,31 @@
-use xv6_defs::c_types::*;
-use xv6_ulib::c_user::*;
-use xv6_ulib::Args;
-use xv6_ulib::CStr;
+#![no_std]
 
-fn run(args: Args) -> i32 {
-    let argc = args.argc();
-    if argc < 2 {
-        writeln!(xv6_ulib::stdout(), "sleep: insufficient arguments").unwrap();
-        return exit(1);
-    } 
+use xv6_ulib::argparse::{parse_int, Arg, ArgError, Parser};
+use xv6_ulib::{eprintln, sleep, Args};
+
+/// Returns the number of ticks to sleep for.
+fn parse(parser: &mut Parser) -> Result<i32, ArgError> {
+    let mut ticks = None;
+    while let Some(arg) = parser.next_arg()? {
+        match arg {
+            Arg::Positional(s) if ticks.is_none() => ticks = Some(parse_int(s.chars_as_str())?),
+            arg => return Err(arg.unexpected()),
+        }
+    }
+    ticks.ok_or(ArgError::MissingArgument("ticks"))
+}
 
-    let ticks = match args.nth(1).data.parse() {
-        Ok(t) => t,
-        Err(_) 
+fn run(args: Args) -> i32 {
+    let mut parser = Parser::new(args, "sleep ticks");
+    let ticks = match parse(&mut parser) {
+        Ok(ticks) => ticks,
+        Err(e) => return parser.usage_error(&e),
     };
-    sleep(time);
+    if let Err(e) = sleep(ticks) {
+        eprintln!("sleep: {}", e);
+        return 1;
+    }
+    0
 }
 
-/// Entry point.
-#[no_mangle]
-pub unsafe extern "C" fn main(argc: c_int, argv: *const *const c_char) -> c_int {
-    xv6_ulib::run_prog(argc, argv, run)
-}
\ No newline at end of file
+xv6_ulib::entry!(run);
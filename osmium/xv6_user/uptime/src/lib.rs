@@ -1,12 +1,10 @@
 #![no_std]
 
-use core::fmt::Write;
-use xv6_defs::c_types::*;
-use xv6_ulib::c_user::*;
+use xv6_ulib::{println, Args};
 
-#[no_mangle]
-pub unsafe extern "C" fn main(_argc: c_int, _argv: *const *const c_char) -> c_int {
-    let ticks = uptime();
-    writeln!(xv6_ulib::stdout(), "{}", ticks).unwrap();
-    exit(0)
+fn run(_args: Args) -> i32 {
+    println!("{}", xv6_ulib::uptime());
+    0
 }
+
+xv6_ulib::entry!(run);
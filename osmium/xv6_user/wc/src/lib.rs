@@ -0,0 +1,56 @@
+#![no_std]
+
+use xv6_ulib::io::{self, Read};
+use xv6_ulib::{println, Args, File};
+
+/// Prints the number of lines, words and bytes in `input`. Returns false on a read error.
+fn wc(mut input: impl Read, name: &str) -> bool {
+    let mut buf = [0; 512];
+    let (mut lines, mut words, mut bytes) = (0, 0, 0);
+    let mut in_word = false;
+    loop {
+        let n = match input.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(_) => {
+                println!("wc: read error");
+                return false;
+            }
+        };
+        for &c in &buf[..n] {
+            bytes += 1;
+            if c == b'\n' {
+                lines += 1;
+            }
+            if b" \r\t\n\x0b".contains(&c) {
+                in_word = false;
+            } else if !in_word {
+                words += 1;
+                in_word = true;
+            }
+        }
+    }
+    println!("{} {} {} {}", lines, words, bytes, name);
+    true
+}
+
+fn run(args: Args) -> i32 {
+    if args.argc() <= 1 {
+        return if wc(io::Fd(0), "") { 0 } else { 1 };
+    }
+    for path in args.skip(1) {
+        let file = match File::open(&path) {
+            Ok(file) => file,
+            Err(_) => {
+                println!("wc: cannot open {}", path.chars_as_str());
+                return 1;
+            }
+        };
+        if !wc(file, path.chars_as_str()) {
+            return 1;
+        }
+    }
+    0
+}
+
+xv6_ulib::entry!(run);
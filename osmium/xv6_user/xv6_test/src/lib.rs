@@ -0,0 +1,179 @@
+//! # xv6_test
+//! Test harness for Rust user programs that test xv6, in the style of `user/usertests.c`.
+//!
+//! Tests are plain functions that panic on failure, e.g. with `assert!`. They are registered with
+//! `tests!`, which also defines the program's entry point:
+//!
+//! ```ignore
+//! fn pipe_roundtrip() {
+//!     let (r, w) = xv6_ulib::pipe().unwrap();
+//!     ...
+//! }
+//!
+//! xv6_test::tests!("rusttests", pipe_roundtrip, big_write: 300);
+//! ```
+//!
+//! Each test runs in its own child process, so a test that panics, is killed or corrupts its
+//! memory can't affect the others. A watchdog process kills tests that run longer than their
+//! timeout, measured with `uptime`. The name of each test is printed followed by `OK`, `FAILED`
+//! or `TIMEOUT`, and the run ends with `ALL TESTS PASSED` or `SOME TESTS FAILED`, which the
+//! `gradelib.py` scripts match on. Arguments select tests to run by name.
+
+#![no_std]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use xv6_ulib::process::{self, ForkResult};
+use xv6_ulib::{print, println, syscall, Args, CStr};
+
+#[doc(hidden)]
+pub use xv6_ulib;
+
+/// Timeout for tests that don't set one, in clock ticks.
+pub const DEFAULT_TIMEOUT: i32 = 100;
+
+/// Exit status of the watchdog when it killed the test.
+const TIMED_OUT: i32 = 2;
+
+/// A registered test.
+pub struct Test {
+    pub name: &'static str,
+    pub func: fn(),
+    /// Ticks the test may run before it is killed.
+    pub timeout: i32,
+}
+
+/// How a test ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Passed,
+    /// The test process exited with the given nonzero status, e.g. `xv6_ulib::PANIC_STATUS`
+    /// after a failed assertion or -1 if it was killed.
+    Failed(i32),
+    TimedOut,
+}
+
+/// Registers tests and defines the entry point of a test program that runs them. Each test is
+/// given as the name of a `fn()`, optionally followed by `: timeout` in ticks.
+#[macro_export]
+macro_rules! tests {
+    ($prog:expr, $($test:ident $(: $timeout:expr)?),* $(,)?) => {
+        const TESTS: &[$crate::Test] = &[
+            $($crate::Test {
+                name: stringify!($test),
+                func: $test,
+                timeout: $crate::tests!(@timeout $($timeout)?),
+            }),*
+        ];
+
+        fn __xv6_test_main(args: $crate::xv6_ulib::Args) -> i32 {
+            $crate::run_tests($prog, TESTS, args)
+        }
+
+        $crate::xv6_ulib::entry!(__xv6_test_main);
+    };
+    (@timeout) => {
+        $crate::DEFAULT_TIMEOUT
+    };
+    (@timeout $timeout:expr) => {
+        $timeout
+    };
+}
+
+/// Runs `test` in a child process, with a watchdog that kills it after its timeout.
+pub fn run_test(test: &Test) -> Outcome {
+    let pid = match process::fork() {
+        Ok(ForkResult::Child) => {
+            (test.func)();
+            syscall::exit(0)
+        }
+        Ok(ForkResult::Parent(pid)) => pid,
+        Err(e) => {
+            println!("runtest: fork error: {}", e);
+            syscall::exit(1)
+        }
+    };
+
+    let deadline = syscall::uptime() + test.timeout;
+    let watchdog = match process::fork() {
+        Ok(ForkResult::Child) => {
+            while syscall::uptime() < deadline {
+                let _ = syscall::sleep(1);
+            }
+            let _ = syscall::kill(pid);
+            syscall::exit(TIMED_OUT)
+        }
+        Ok(ForkResult::Parent(watchdog)) => Some(watchdog),
+        // Run without a timeout rather than not at all.
+        Err(_) => None,
+    };
+
+    let mut status = None;
+    let mut reaped_at = 0;
+    let mut watchdog_status = None;
+    while status.is_none() || (watchdog.is_some() && watchdog_status.is_none()) {
+        let (wpid, st) = match process::wait() {
+            Ok(result) => result,
+            Err(_) => break,
+        };
+        if wpid == pid {
+            status = Some(st);
+            reaped_at = syscall::uptime();
+            if let Some(watchdog) = watchdog {
+                // Does nothing if the watchdog has already exited.
+                let _ = syscall::kill(watchdog);
+            }
+        } else if Some(wpid) == watchdog {
+            watchdog_status = Some(st);
+        }
+    }
+
+    // The watchdog may be killed before it can exit with TIMED_OUT, so also count a test that was
+    // killed after its deadline as timed out.
+    match (status, watchdog_status) {
+        (_, Some(TIMED_OUT)) => Outcome::TimedOut,
+        (Some(-1), _) if reaped_at >= deadline => Outcome::TimedOut,
+        (Some(0), _) => Outcome::Passed,
+        (Some(st), _) => Outcome::Failed(st),
+        (None, _) => Outcome::Failed(-1),
+    }
+}
+
+/// Runs the tests named in `args`, or all of them if there are no arguments, and prints a
+/// summary. Returns the exit status for the test program.
+pub fn run_tests(prog: &str, tests: &[Test], args: Args) -> i32 {
+    let selected: Vec<CStr<'static>> = args.skip(1).collect();
+
+    println!("{} starting", prog);
+    let (mut passed, mut failed) = (0, 0);
+    for test in tests {
+        if !selected.is_empty() && !selected.iter().any(|arg| arg.chars_as_str() == test.name) {
+            continue;
+        }
+        print!("test {}: ", test.name);
+        match run_test(test) {
+            Outcome::Passed => {
+                println!("OK");
+                passed += 1;
+            }
+            Outcome::Failed(status) => {
+                println!("FAILED (status {})", status);
+                failed += 1;
+            }
+            Outcome::TimedOut => {
+                println!("TIMEOUT");
+                failed += 1;
+            }
+        }
+    }
+
+    println!("{}: {} passed, {} failed", prog, passed, failed);
+    if failed == 0 {
+        println!("ALL TESTS PASSED");
+        0
+    } else {
+        println!("SOME TESTS FAILED");
+        1
+    }
+}
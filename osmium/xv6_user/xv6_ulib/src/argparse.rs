@@ -0,0 +1,169 @@
+//! Command line argument parsing shared by the Rust utilities.
+//!
+//! `Parser` splits the arguments into short flags and positional arguments, getopt style: flags
+//! can be grouped (`-ab`), a flag's value can be attached (`-n5`) or follow it (`-n 5`), a lone
+//! `-` is positional, and everything after `--` is positional. Errors are returned as `ArgError`
+//! and reported together with the program's usage line by `Parser::usage_error`.
+//!
+//! ```ignore
+//! let mut parser = Parser::new(args, "wc [-l] [file ...]");
+//! while let Some(arg) = parser.next_arg()? {
+//!     match arg {
+//!         Arg::Short('l') => lines_only = true,
+//!         Arg::Positional(path) => files.push(path),
+//!         arg => return Err(arg.unexpected()),
+//!     }
+//! }
+//! ```
+
+use crate::{eprintln, Args, CStr};
+use alloc::string::{String, ToString};
+use core::fmt;
+use core::str::FromStr;
+
+/// Exit status for a command line that couldn't be parsed, the same status the C utilities use.
+pub const USAGE_STATUS: i32 = 1;
+
+/// An argument returned by `Parser::next_arg`.
+pub enum Arg {
+    /// A short flag, e.g. `l` for `-l`.
+    Short(char),
+    /// An argument that isn't a flag.
+    Positional(CStr<'static>),
+}
+
+impl Arg {
+    /// Returns the error for an argument the program doesn't accept.
+    pub fn unexpected(self) -> ArgError {
+        match self {
+            Arg::Short(c) => ArgError::UnknownFlag(c),
+            Arg::Positional(s) => ArgError::UnexpectedArgument(without_nul(&s).to_string()),
+        }
+    }
+}
+
+/// Reasons a command line couldn't be parsed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ArgError {
+    /// The flag isn't accepted by the program.
+    UnknownFlag(char),
+    /// The flag takes a value but none was given.
+    MissingValue(char),
+    /// A required positional argument, named by the string, is missing.
+    MissingArgument(&'static str),
+    /// There are more positional arguments than the program accepts.
+    UnexpectedArgument(String),
+    /// The argument should have been a number.
+    InvalidNumber(String),
+}
+
+impl fmt::Display for ArgError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ArgError::UnknownFlag(c) => write!(f, "unknown option -{}", c),
+            ArgError::MissingValue(c) => write!(f, "option -{} needs a value", c),
+            ArgError::MissingArgument(name) => write!(f, "missing {}", name),
+            ArgError::UnexpectedArgument(s) => write!(f, "unexpected argument '{}'", s),
+            ArgError::InvalidNumber(s) => write!(f, "invalid number '{}'", s),
+        }
+    }
+}
+
+/// Parses `s` as an integer of type `T`.
+///
+/// # Errors
+/// Returns `ArgError::InvalidNumber` if `s` isn't a number or doesn't fit in `T`.
+pub fn parse_int<T: FromStr>(s: &str) -> Result<T, ArgError> {
+    s.parse()
+        .map_err(|_| ArgError::InvalidNumber(s.to_string()))
+}
+
+/// Returns the argument without its null terminator, keeping the `'static` lifetime.
+fn without_nul(s: &CStr<'static>) -> &'static str {
+    let data: &'static str = s.data;
+    &data[..data.len() - 1]
+}
+
+/// Splits a program's arguments into flags and positional arguments.
+pub struct Parser {
+    args: Args,
+    prog: &'static str,
+    usage: &'static str,
+    /// Flags left in the current group, e.g. `"bc"` after returning `a` from `-abc`.
+    shorts: &'static str,
+    /// The last flag returned, for error messages from `value`.
+    last_flag: Option<char>,
+    /// Whether `--` has been seen.
+    only_positional: bool,
+}
+
+impl Parser {
+    /// Returns a parser for `args`. `usage` is the synopsis printed by `usage_error`, without the
+    /// leading "usage: ".
+    pub fn new(mut args: Args, usage: &'static str) -> Parser {
+        let prog = args.next().map_or("", |s| without_nul(&s));
+        Parser {
+            args,
+            prog,
+            usage,
+            shorts: "",
+            last_flag: None,
+            only_positional: false,
+        }
+    }
+
+    /// Returns the program name, i.e. the first argument.
+    pub fn prog(&self) -> &'static str {
+        self.prog
+    }
+
+    /// Returns the next flag or positional argument, or `None` once all arguments are used.
+    pub fn next_arg(&mut self) -> Result<Option<Arg>, ArgError> {
+        if let Some(c) = self.shorts.chars().next() {
+            self.shorts = &self.shorts[c.len_utf8()..];
+            self.last_flag = Some(c);
+            return Ok(Some(Arg::Short(c)));
+        }
+        self.last_flag = None;
+        let arg = match self.args.next() {
+            Some(arg) => arg,
+            None => return Ok(None),
+        };
+        let s = without_nul(&arg);
+        if self.only_positional || s == "-" || !s.starts_with('-') {
+            return Ok(Some(Arg::Positional(arg)));
+        }
+        if s == "--" {
+            self.only_positional = true;
+            return self.next_arg();
+        }
+        self.shorts = &s[1..];
+        self.next_arg()
+    }
+
+    /// Returns the value of the flag just returned by `next_arg`: the rest of its group if there is
+    /// one (`-n5`), otherwise the next argument (`-n 5`).
+    ///
+    /// # Errors
+    /// Returns `ArgError::MissingValue` if there is no value.
+    pub fn value(&mut self) -> Result<&'static str, ArgError> {
+        let flag = self.last_flag.take().unwrap_or('-');
+        if !self.shorts.is_empty() {
+            let value = self.shorts;
+            self.shorts = "";
+            return Ok(value);
+        }
+        match self.args.next() {
+            Some(arg) => Ok(without_nul(&arg)),
+            None => Err(ArgError::MissingValue(flag)),
+        }
+    }
+
+    /// Prints `err` and the usage line to standard error. Returns `USAGE_STATUS` so a program can
+    /// `return parser.usage_error(&e)` from its main function.
+    pub fn usage_error(&self, err: &ArgError) -> i32 {
+        eprintln!("{}: {}", self.prog, err);
+        eprintln!("usage: {}", self.usage);
+        USAGE_STATUS
+    }
+}
@@ -0,0 +1,79 @@
+//! Raw system call stubs.
+//!
+//! These issue `ecall` directly, following the same convention as the stubs `user/usys.pl`
+//! generates for C programs: the system call number goes in `a7`, arguments in `a0`-`a2`, and the
+//! kernel's return value comes back in `a0`. The numbers come from `xv6_defs::syscall`, which is
+//! generated from `kernel/syscall.h`.
+//!
+//! Most code should use the safe wrappers in `syscall` instead.
+
+#[cfg(target_arch = "riscv64")]
+mod imp {
+    use core::arch::asm;
+
+    #[inline(always)]
+    pub(crate) unsafe fn syscall0(num: usize) -> isize {
+        let ret: isize;
+        asm!("ecall", lateout("a0") ret, in("a7") num, options(nostack));
+        ret
+    }
+
+    #[inline(always)]
+    pub(crate) unsafe fn syscall1(num: usize, a0: usize) -> isize {
+        let ret: isize;
+        asm!("ecall", inlateout("a0") a0 as isize => ret, in("a7") num, options(nostack));
+        ret
+    }
+
+    #[inline(always)]
+    pub(crate) unsafe fn syscall2(num: usize, a0: usize, a1: usize) -> isize {
+        let ret: isize;
+        asm!(
+            "ecall",
+            inlateout("a0") a0 as isize => ret,
+            in("a1") a1,
+            in("a7") num,
+            options(nostack)
+        );
+        ret
+    }
+
+    #[inline(always)]
+    pub(crate) unsafe fn syscall3(num: usize, a0: usize, a1: usize, a2: usize) -> isize {
+        let ret: isize;
+        asm!(
+            "ecall",
+            inlateout("a0") a0 as isize => ret,
+            in("a1") a1,
+            in("a2") a2,
+            in("a7") num,
+            options(nostack)
+        );
+        ret
+    }
+}
+
+// Lets the library be built and tested on other architectures. There is no kernel to call there,
+// so every system call fails with `ENOSYS`.
+#[cfg(not(target_arch = "riscv64"))]
+mod imp {
+    use xv6_defs::errno::ENOSYS;
+
+    pub(crate) unsafe fn syscall0(_num: usize) -> isize {
+        -(ENOSYS as isize)
+    }
+
+    pub(crate) unsafe fn syscall1(num: usize, _a0: usize) -> isize {
+        syscall0(num)
+    }
+
+    pub(crate) unsafe fn syscall2(num: usize, _a0: usize, _a1: usize) -> isize {
+        syscall0(num)
+    }
+
+    pub(crate) unsafe fn syscall3(num: usize, _a0: usize, _a1: usize, _a2: usize) -> isize {
+        syscall0(num)
+    }
+}
+
+pub(crate) use imp::*;
@@ -0,0 +1,195 @@
+//! Error type for failed system calls.
+//!
+//! The kernel reports failures by returning a negated error number from `kernel/errno.h`. `Error`
+//! decodes these so callers can match on the cause of a failure and print a useful message.
+
+use core::fmt;
+use xv6_defs::errno::*;
+
+/// The cause of a failed system call.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The operation is not permitted on this file or process (`EPERM`).
+    NotPermitted,
+    /// A path component does not exist (`ENOENT`).
+    NotFound,
+    /// No process has the given PID (`ESRCH`).
+    NoSuchProcess,
+    /// The process was killed while blocked in the system call (`EINTR`).
+    Interrupted,
+    /// The device reported an error (`EIO`).
+    Io,
+    /// The inode refers to a device that doesn't exist (`ENXIO`).
+    NoSuchDevice,
+    /// Too many arguments were passed to `exec` (`E2BIG`).
+    ArgListTooLong,
+    /// The file passed to `exec` is not a valid executable (`ENOEXEC`).
+    ExecFormat,
+    /// The file descriptor is not open (`EBADF`).
+    BadFileDescriptor,
+    /// The process has no children to wait for (`ECHILD`).
+    NoChildren,
+    /// The kernel is temporarily out of some resource, e.g. process slots (`EAGAIN`).
+    WouldBlock,
+    /// The kernel or process ran out of memory (`ENOMEM`).
+    OutOfMemory,
+    /// A pointer passed to the kernel was invalid (`EFAULT`).
+    BadAddress,
+    /// The path already exists (`EEXIST`).
+    AlreadyExists,
+    /// A link was attempted across devices (`EXDEV`).
+    CrossDevice,
+    /// A path component that must be a directory is not one (`ENOTDIR`).
+    NotADirectory,
+    /// The operation is not valid on a directory (`EISDIR`).
+    IsADirectory,
+    /// An argument was invalid (`EINVAL`).
+    InvalidArgument,
+    /// The system-wide file table is full (`ENFILE`).
+    FileTableFull,
+    /// The process has no free file descriptors (`EMFILE`).
+    TooManyOpenFiles,
+    /// A file would grow past `MAXFILE` blocks (`EFBIG`).
+    FileTooLarge,
+    /// The disk is full (`ENOSPC`).
+    NoSpace,
+    /// The read end of a pipe was closed (`EPIPE`).
+    BrokenPipe,
+    /// A path was longer than `MAXPATH` (`ENAMETOOLONG`).
+    NameTooLong,
+    /// There is no such system call (`ENOSYS`).
+    Unsupported,
+    /// A directory to be removed still has entries (`ENOTEMPTY`).
+    DirectoryNotEmpty,
+    /// The end of a file was reached before the requested amount of data was read. Reported by
+    /// the library rather than the kernel; its error number is `EIO`.
+    UnexpectedEof,
+    /// An error number this library doesn't know about.
+    Unknown(i32),
+}
+
+impl Error {
+    /// Converts a positive error number into an `Error`.
+    pub fn from_errno(errno: i32) -> Error {
+        match errno {
+            EPERM => Error::NotPermitted,
+            ENOENT => Error::NotFound,
+            ESRCH => Error::NoSuchProcess,
+            EINTR => Error::Interrupted,
+            EIO => Error::Io,
+            ENXIO => Error::NoSuchDevice,
+            E2BIG => Error::ArgListTooLong,
+            ENOEXEC => Error::ExecFormat,
+            EBADF => Error::BadFileDescriptor,
+            ECHILD => Error::NoChildren,
+            EAGAIN => Error::WouldBlock,
+            ENOMEM => Error::OutOfMemory,
+            EFAULT => Error::BadAddress,
+            EEXIST => Error::AlreadyExists,
+            EXDEV => Error::CrossDevice,
+            ENOTDIR => Error::NotADirectory,
+            EISDIR => Error::IsADirectory,
+            EINVAL => Error::InvalidArgument,
+            ENFILE => Error::FileTableFull,
+            EMFILE => Error::TooManyOpenFiles,
+            EFBIG => Error::FileTooLarge,
+            ENOSPC => Error::NoSpace,
+            EPIPE => Error::BrokenPipe,
+            ENAMETOOLONG => Error::NameTooLong,
+            ENOSYS => Error::Unsupported,
+            ENOTEMPTY => Error::DirectoryNotEmpty,
+            n => Error::Unknown(n),
+        }
+    }
+
+    /// Returns the error number corresponding to this error.
+    pub fn errno(&self) -> i32 {
+        match *self {
+            Error::NotPermitted => EPERM,
+            Error::NotFound => ENOENT,
+            Error::NoSuchProcess => ESRCH,
+            Error::Interrupted => EINTR,
+            Error::Io => EIO,
+            Error::NoSuchDevice => ENXIO,
+            Error::ArgListTooLong => E2BIG,
+            Error::ExecFormat => ENOEXEC,
+            Error::BadFileDescriptor => EBADF,
+            Error::NoChildren => ECHILD,
+            Error::WouldBlock => EAGAIN,
+            Error::OutOfMemory => ENOMEM,
+            Error::BadAddress => EFAULT,
+            Error::AlreadyExists => EEXIST,
+            Error::CrossDevice => EXDEV,
+            Error::NotADirectory => ENOTDIR,
+            Error::IsADirectory => EISDIR,
+            Error::InvalidArgument => EINVAL,
+            Error::FileTableFull => ENFILE,
+            Error::TooManyOpenFiles => EMFILE,
+            Error::FileTooLarge => EFBIG,
+            Error::NoSpace => ENOSPC,
+            Error::BrokenPipe => EPIPE,
+            Error::NameTooLong => ENAMETOOLONG,
+            Error::Unsupported => ENOSYS,
+            Error::DirectoryNotEmpty => ENOTEMPTY,
+            Error::UnexpectedEof => EIO,
+            Error::Unknown(n) => n,
+        }
+    }
+
+    /// Returns a short lowercase description of the error, suitable for diagnostics such as
+    /// `cat: cannot open foo: no such file`.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Error::NotPermitted => "operation not permitted",
+            Error::NotFound => "no such file",
+            Error::NoSuchProcess => "no such process",
+            Error::Interrupted => "interrupted",
+            Error::Io => "i/o error",
+            Error::NoSuchDevice => "no such device",
+            Error::ArgListTooLong => "argument list too long",
+            Error::ExecFormat => "exec format error",
+            Error::BadFileDescriptor => "bad file descriptor",
+            Error::NoChildren => "no child processes",
+            Error::WouldBlock => "resource temporarily unavailable",
+            Error::OutOfMemory => "out of memory",
+            Error::BadAddress => "bad address",
+            Error::AlreadyExists => "file exists",
+            Error::CrossDevice => "cross-device link",
+            Error::NotADirectory => "not a directory",
+            Error::IsADirectory => "is a directory",
+            Error::InvalidArgument => "invalid argument",
+            Error::FileTableFull => "file table full",
+            Error::TooManyOpenFiles => "too many open files",
+            Error::FileTooLarge => "file too large",
+            Error::NoSpace => "no space left on device",
+            Error::BrokenPipe => "broken pipe",
+            Error::NameTooLong => "file name too long",
+            Error::Unsupported => "function not implemented",
+            Error::DirectoryNotEmpty => "directory not empty",
+            Error::UnexpectedEof => "unexpected end of file",
+            Error::Unknown(_) => "unknown error",
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Error::Unknown(n) => write!(f, "unknown error {}", n),
+            _ => f.write_str(self.as_str()),
+        }
+    }
+}
+
+/// Result type for fallible system calls.
+pub type Result<T> = core::result::Result<T, Error>;
+
+/// Converts the raw return value of a system call into a `Result`. Non-negative values are
+/// returned unchanged; negative values are decoded as negated error numbers.
+pub(crate) fn check(ret: isize) -> Result<usize> {
+    if ret >= 0 {
+        Ok(ret as usize)
+    } else {
+        Err(Error::from_errno(-ret as i32))
+    }
+}
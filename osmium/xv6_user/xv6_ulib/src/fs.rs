@@ -0,0 +1,325 @@
+//! Owned file descriptors.
+//!
+//! A `File` owns an open file descriptor and closes it when dropped, so programs can't leak
+//! descriptors on early returns. Files are opened with the flags from `kernel/fcntl.h`, chosen
+//! through `OpenOptions`. xv6 has no `lseek`, so files are always read and written sequentially.
+//!
+//! Directories are read with `read_dir`, which decodes the raw `struct dirent` records.
+
+use crate::error::{Error, Result};
+use crate::io::{Read, Write};
+use crate::stat::{FileType, Stat};
+use crate::syscall;
+use crate::CStr;
+use alloc::vec::Vec;
+use core::cell::Cell;
+use core::{mem, slice, str};
+use xv6_defs::fcntl::*;
+use xv6_defs::fs::{Dirent, DIRSIZ};
+use xv6_defs::param::MAXPATH;
+
+/// An open file descriptor that is closed on drop. Can refer to a file, device or pipe.
+#[derive(Debug)]
+pub struct File {
+    fd: i32,
+}
+
+impl File {
+    /// Opens the file at `path` for reading.
+    pub fn open(path: &CStr) -> Result<File> {
+        OpenOptions::new().read(true).open(path)
+    }
+
+    /// Opens the file at `path` for writing, creating it if it doesn't exist and truncating it if
+    /// it does.
+    pub fn create(path: &CStr) -> Result<File> {
+        OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)
+    }
+
+    /// Returns a new `OpenOptions` for choosing how to open a file.
+    pub fn options() -> OpenOptions {
+        OpenOptions::new()
+    }
+
+    /// Takes ownership of the open file descriptor `fd`. It will be closed when the `File` is
+    /// dropped.
+    pub fn from_raw_fd(fd: i32) -> File {
+        File { fd }
+    }
+
+    /// Returns the file descriptor without giving up ownership.
+    pub fn as_raw_fd(&self) -> i32 {
+        self.fd
+    }
+
+    /// Gives up ownership of the file descriptor and returns it. The caller becomes responsible
+    /// for closing it.
+    pub fn into_raw_fd(self) -> i32 {
+        let fd = self.fd;
+        mem::forget(self);
+        fd
+    }
+
+    /// Returns a new `File` referring to the same open file, using `dup`.
+    pub fn try_clone(&self) -> Result<File> {
+        syscall::dup(self.fd).map(File::from_raw_fd)
+    }
+
+    /// Returns the file's metadata.
+    pub fn metadata(&self) -> Result<Stat> {
+        syscall::fstat(self.fd)
+    }
+}
+
+impl Drop for File {
+    fn drop(&mut self) {
+        // Nothing useful can be done if close fails.
+        let _ = syscall::close(self.fd);
+    }
+}
+
+impl Read for File {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        syscall::read(self.fd, buf)
+    }
+}
+
+impl Write for File {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        syscall::write(self.fd, buf)
+    }
+}
+
+/// Builder for the flags used to open a file.
+///
+/// ```ignore
+/// let log = File::options().write(true).create(true).open(&path)?;
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct OpenOptions {
+    read: bool,
+    write: bool,
+    create: bool,
+    truncate: bool,
+    append: bool,
+}
+
+impl OpenOptions {
+    /// Returns options with every flag unset. At least one of `read` and `write` must be set
+    /// before opening; if neither is, the file is opened for reading.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Opens the file for reading.
+    pub fn read(&mut self, read: bool) -> &mut Self {
+        self.read = read;
+        self
+    }
+
+    /// Opens the file for writing.
+    pub fn write(&mut self, write: bool) -> &mut Self {
+        self.write = write;
+        self
+    }
+
+    /// Creates the file if it doesn't exist (`O_CREATE`).
+    pub fn create(&mut self, create: bool) -> &mut Self {
+        self.create = create;
+        self
+    }
+
+    /// Truncates the file to zero length if it exists (`O_TRUNC`).
+    pub fn truncate(&mut self, truncate: bool) -> &mut Self {
+        self.truncate = truncate;
+        self
+    }
+
+    /// Positions the file at its end after opening, so writes are added to the end. xv6 has no
+    /// `O_APPEND` or `lseek`, but reads and writes share the file offset, so a regular file is
+    /// opened for reading as well and read to the end. Writes by other processes after opening
+    /// are overwritten.
+    pub fn append(&mut self, append: bool) -> &mut Self {
+        self.append = append;
+        self
+    }
+
+    /// Returns the `open` flags for these options.
+    pub fn flags(&self) -> i32 {
+        let mut flags = match (self.read || self.append, self.write || self.append) {
+            (true, true) => O_RDWR,
+            (false, true) => O_WRONLY,
+            (_, false) => O_RDONLY,
+        };
+        if self.create {
+            flags |= O_CREATE;
+        }
+        if self.truncate {
+            flags |= O_TRUNC;
+        }
+        flags
+    }
+
+    /// Opens the file at `path` with these options.
+    pub fn open(&self, path: &CStr) -> Result<File> {
+        let mut file = syscall::open(path, self.flags()).map(File::from_raw_fd)?;
+        // Reading a device such as the console would wait for input instead.
+        if self.append && !self.truncate && file.metadata()?.is_file() {
+            let mut buf = [0; 512];
+            while file.read(&mut buf)? > 0 {}
+        }
+        Ok(file)
+    }
+}
+
+/// Creates a pipe. Returns the read end and the write end, in that order.
+pub fn pipe() -> Result<(File, File)> {
+    let (r, w) = syscall::pipe()?;
+    Ok((File::from_raw_fd(r), File::from_raw_fd(w)))
+}
+
+/// Returns an iterator over the entries of the directory at `path`. Free slots are skipped, but
+/// `.` and `..` are included.
+///
+/// ```ignore
+/// for entry in fs::read_dir(&cstr!("."))? {
+///     let entry = entry?;
+///     println!("{} {}", entry.name(), entry.ino());
+/// }
+/// ```
+///
+/// # Errors
+/// Returns `Error::NotADirectory` if `path` isn't a directory, or the error from opening it.
+pub fn read_dir(path: &CStr) -> Result<ReadDir> {
+    let file = File::open(path)?;
+    if !file.metadata()?.is_dir() {
+        return Err(Error::NotADirectory);
+    }
+    let mut dir = Vec::from(path.as_bytes());
+    // Drop the null terminator.
+    dir.pop();
+    if dir.last() != Some(&b'/') {
+        dir.push(b'/');
+    }
+    Ok(ReadDir { file, dir })
+}
+
+/// Iterator over the entries of a directory, returned by `read_dir`.
+#[derive(Debug)]
+pub struct ReadDir {
+    file: File,
+    /// Directory path with a trailing `/`, used to build the entries' paths.
+    dir: Vec<u8>,
+}
+
+impl ReadDir {
+    /// Reads the next raw record. Returns `None` at the end of the directory.
+    fn next_dirent(&mut self) -> Option<Result<Dirent>> {
+        let mut de = Dirent::default();
+        let buf = unsafe {
+            slice::from_raw_parts_mut(&mut de as *mut Dirent as *mut u8, mem::size_of::<Dirent>())
+        };
+        match self.file.read(buf) {
+            Ok(n) if n == buf.len() => Some(Ok(de)),
+            // A short read means the directory's size isn't a multiple of the record size, which
+            // ls treats as the end too.
+            Ok(_) => None,
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+impl Iterator for ReadDir {
+    type Item = Result<DirEntry>;
+
+    fn next(&mut self) -> Option<Result<DirEntry>> {
+        loop {
+            let de = match self.next_dirent()? {
+                Ok(de) => de,
+                Err(e) => return Some(Err(e)),
+            };
+            if de.inum == 0 {
+                continue;
+            }
+            let len = de.name.iter().position(|&c| c == 0).unwrap_or(DIRSIZ);
+            let mut path = Vec::with_capacity(self.dir.len() + len + 1);
+            path.extend_from_slice(&self.dir);
+            path.extend_from_slice(&de.name[..len]);
+            path.push(0);
+            return Some(Ok(DirEntry {
+                ino: de.inum as u32,
+                path,
+                name_start: self.dir.len(),
+                stat: Cell::new(None),
+            }));
+        }
+    }
+}
+
+/// An entry in a directory. Its metadata is only fetched, with `stat`, when first asked for.
+#[derive(Debug)]
+pub struct DirEntry {
+    ino: u32,
+    /// Path of the entry with a null terminator, i.e. the directory path, `/` and the name.
+    path: Vec<u8>,
+    name_start: usize,
+    stat: Cell<Option<Stat>>,
+}
+
+impl DirEntry {
+    /// Returns the inode number recorded in the directory.
+    pub fn ino(&self) -> u32 {
+        self.ino
+    }
+
+    /// Returns the raw bytes of the entry's name, at most `DIRSIZ` long.
+    pub fn name_bytes(&self) -> &[u8] {
+        &self.path[self.name_start..self.path.len() - 1]
+    }
+
+    /// Returns the entry's name. Names that aren't valid UTF-8 are cut off at the first invalid
+    /// byte; use `name_bytes` to get the whole name.
+    pub fn name(&self) -> &str {
+        let name = self.name_bytes();
+        match str::from_utf8(name) {
+            Ok(name) => name,
+            Err(e) => unsafe { str::from_utf8_unchecked(&name[..e.valid_up_to()]) },
+        }
+    }
+
+    /// Returns the path of the entry: the path given to `read_dir` joined with the name.
+    pub fn path(&self) -> &str {
+        let path = &self.path[..self.path.len() - 1];
+        match str::from_utf8(path) {
+            Ok(path) => path,
+            Err(e) => unsafe { str::from_utf8_unchecked(&path[..e.valid_up_to()]) },
+        }
+    }
+
+    /// Returns the entry's metadata, calling `stat` on its path the first time.
+    ///
+    /// # Errors
+    /// Returns `Error::NameTooLong` if the path is longer than `MAXPATH`, `Error::InvalidArgument`
+    /// if it isn't ASCII, or the error from `stat`, e.g. if the entry was removed.
+    pub fn metadata(&self) -> Result<Stat> {
+        if let Some(st) = self.stat.get() {
+            return Ok(st);
+        }
+        if self.path.len() > MAXPATH {
+            return Err(Error::NameTooLong);
+        }
+        let path = str::from_utf8(&self.path).map_err(|_| Error::InvalidArgument)?;
+        let st = syscall::stat(&CStr::try_new(path)?)?;
+        self.stat.set(Some(st));
+        Ok(st)
+    }
+
+    /// Returns the type of the entry. Fetches the metadata if it hasn't been already.
+    pub fn file_type(&self) -> Result<FileType> {
+        self.metadata().map(|st| st.file_type())
+    }
+}
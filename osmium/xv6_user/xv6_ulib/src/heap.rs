@@ -0,0 +1,297 @@
+//! Heap allocator for user programs, backed by the `sbrk` system call.
+//!
+//! Free memory is kept in a singly linked list of blocks sorted by address, in the style of
+//! `user/umalloc.c`. Allocation is first-fit: a free block large enough for the request is split
+//! and the remainder stays on the list. Freed blocks are merged with their neighbours so the heap
+//! doesn't fragment into pieces too small to reuse. When no block fits, the heap grows with `sbrk`
+//! and the new memory is freed into the list.
+//!
+//! The allocator is registered as the `#[global_allocator]`, so programs can use `Vec`, `String`
+//! and `Box` after adding `extern crate alloc;`.
+
+use crate::syscall;
+use core::alloc::{GlobalAlloc, Layout};
+use core::cell::UnsafeCell;
+use core::mem;
+use core::ptr;
+
+/// Header at the start of every block, free or allocated. Block sizes are measured in units of
+/// `size_of::<Header>()` bytes, so every block and every payload is 16-byte aligned.
+#[repr(C, align(16))]
+struct Header {
+    /// Next free block in address order. Unused while the block is allocated.
+    next: *mut Header,
+    /// Size of the block in units, including this header.
+    size: usize,
+}
+
+const UNIT: usize = mem::size_of::<Header>();
+
+/// Minimum number of bytes to request from the kernel at once.
+const MIN_GROW: usize = 4096;
+
+/// First-fit allocator over an address-ordered free list.
+pub struct Heap {
+    free: UnsafeCell<*mut Header>,
+}
+
+// User programs are single threaded, so the free list is never accessed concurrently.
+unsafe impl Sync for Heap {}
+
+// Host unit tests keep the host allocator.
+#[cfg(not(test))]
+#[global_allocator]
+static HEAP: Heap = Heap::new();
+
+impl Heap {
+    /// Returns an empty heap. Memory is requested from the kernel on the first allocation.
+    pub const fn new() -> Self {
+        Heap {
+            free: UnsafeCell::new(ptr::null_mut()),
+        }
+    }
+
+    /// Returns the number of units needed to hold `layout`, including the header.
+    fn units_for(layout: &Layout) -> usize {
+        layout.size().max(1).div_ceil(UNIT) + 1
+    }
+
+    /// Searches the free list for a block that can hold `units` units with a payload aligned to
+    /// `align`, removes it from the list and returns its header. Returns null if nothing fits.
+    unsafe fn take(&self, units: usize, align: usize) -> *mut Header {
+        let mut prev: *mut *mut Header = self.free.get();
+        let mut blk = *prev;
+        while !blk.is_null() {
+            // Number of units to skip at the front of the block so that the payload is aligned.
+            let payload = blk.add(1) as usize;
+            let gap = (align_up(payload, align) - payload) / UNIT;
+            if (*blk).size >= gap + units {
+                if gap > 0 {
+                    // Leave the misaligned front of the block on the free list.
+                    let rest = blk.add(gap);
+                    (*rest).size = (*blk).size - gap;
+                    (*rest).next = (*blk).next;
+                    (*blk).size = gap;
+                    (*blk).next = rest;
+                    prev = &mut (*blk).next;
+                    blk = rest;
+                }
+                if (*blk).size > units {
+                    // Return the tail of the block to the free list.
+                    let tail = blk.add(units);
+                    (*tail).size = (*blk).size - units;
+                    (*tail).next = (*blk).next;
+                    *prev = tail;
+                    (*blk).size = units;
+                } else {
+                    *prev = (*blk).next;
+                }
+                return blk;
+            }
+            prev = &mut (*blk).next;
+            blk = *prev;
+        }
+        ptr::null_mut()
+    }
+
+    /// Inserts `blk` into the free list, merging it with adjacent free blocks.
+    unsafe fn insert(&self, blk: *mut Header) {
+        let mut prev: *mut Header = ptr::null_mut();
+        let mut next = *self.free.get();
+        while !next.is_null() && next < blk {
+            prev = next;
+            next = (*next).next;
+        }
+
+        (*blk).next = next;
+        if !next.is_null() && blk.add((*blk).size) == next {
+            (*blk).size += (*next).size;
+            (*blk).next = (*next).next;
+        }
+
+        if prev.is_null() {
+            *self.free.get() = blk;
+        } else if prev.add((*prev).size) == blk {
+            (*prev).size += (*blk).size;
+            (*prev).next = (*blk).next;
+        } else {
+            (*prev).next = blk;
+        }
+    }
+
+    /// Grows the heap by at least `units` units plus enough slack to align a payload to `align`.
+    /// Returns false if the kernel is out of memory.
+    unsafe fn grow(&self, units: usize, align: usize) -> bool {
+        let mut nbytes = units * UNIT + align;
+        if nbytes < MIN_GROW {
+            nbytes = MIN_GROW;
+        }
+        if nbytes > i32::MAX as usize {
+            return false;
+        }
+        let p = match syscall::sbrk(nbytes as i32) {
+            Ok(p) => p,
+            Err(_) => return false,
+        };
+        // The break is normally page aligned, but don't rely on it.
+        let start = align_up(p as usize, UNIT);
+        let end = p as usize + nbytes;
+        let blk = start as *mut Header;
+        (*blk).size = (end - start) / UNIT;
+        self.insert(blk);
+        true
+    }
+}
+
+impl Default for Heap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+unsafe impl GlobalAlloc for Heap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let units = Self::units_for(&layout);
+        let align = if layout.align() > UNIT {
+            layout.align()
+        } else {
+            UNIT
+        };
+        let mut blk = self.take(units, align);
+        if blk.is_null() {
+            if !self.grow(units, align) {
+                return ptr::null_mut();
+            }
+            blk = self.take(units, align);
+            if blk.is_null() {
+                return ptr::null_mut();
+            }
+        }
+        blk.add(1) as *mut u8
+    }
+
+    unsafe fn dealloc(&self, p: *mut u8, _layout: Layout) {
+        let blk = (p as *mut Header).sub(1);
+        self.insert(blk);
+    }
+}
+
+/// Rounds `addr` up to a multiple of `align`, which must be a power of two.
+fn align_up(addr: usize, align: usize) -> usize {
+    (addr + align - 1) & !(align - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec::Vec;
+
+    /// Returns a heap whose free list holds one block of `units` units of `mem`.
+    fn heap(mem: &mut Vec<Header>, units: usize) -> Heap {
+        mem.resize_with(units, || Header {
+            next: ptr::null_mut(),
+            size: 0,
+        });
+        let heap = Heap::new();
+        let blk = mem.as_mut_ptr();
+        unsafe {
+            (*blk).size = units;
+            heap.insert(blk);
+        }
+        heap
+    }
+
+    /// Returns the (offset in units from `base`, size) of each block on the free list.
+    fn free_blocks(heap: &Heap, base: *mut Header) -> Vec<(usize, usize)> {
+        let mut blocks = Vec::new();
+        unsafe {
+            let mut blk = *heap.free.get();
+            while !blk.is_null() {
+                blocks.push((blk.offset_from(base) as usize, (*blk).size));
+                blk = (*blk).next;
+            }
+        }
+        blocks
+    }
+
+    #[test]
+    fn units_include_header() {
+        let units = |size| Heap::units_for(&Layout::from_size_align(size, 1).unwrap());
+        assert_eq!(units(0), 2);
+        assert_eq!(units(1), 2);
+        assert_eq!(units(UNIT), 2);
+        assert_eq!(units(UNIT + 1), 3);
+    }
+
+    #[test]
+    fn take_splits_block() {
+        let mut mem = Vec::new();
+        let heap = heap(&mut mem, 16);
+        let base = mem.as_mut_ptr();
+        unsafe {
+            assert_eq!(heap.take(4, UNIT), base);
+            assert_eq!((*base).size, 4);
+            assert_eq!(free_blocks(&heap, base), [(4, 12)]);
+            assert_eq!(heap.take(12, UNIT), base.add(4));
+            assert!(free_blocks(&heap, base).is_empty());
+            assert!(heap.take(1, UNIT).is_null());
+        }
+    }
+
+    #[test]
+    fn take_is_first_fit() {
+        let mut mem = Vec::new();
+        let heap = heap(&mut mem, 16);
+        let base = mem.as_mut_ptr();
+        unsafe {
+            let a = heap.take(2, UNIT);
+            let b = heap.take(6, UNIT);
+            let _c = heap.take(2, UNIT);
+            heap.insert(a);
+            heap.insert(b);
+            // a and b are adjacent, so they merge.
+            assert_eq!(free_blocks(&heap, base), [(0, 8), (10, 6)]);
+            assert!(heap.take(9, UNIT).is_null());
+            assert_eq!(heap.take(5, UNIT), base);
+        }
+    }
+
+    #[test]
+    fn insert_merges_neighbours() {
+        let mut mem = Vec::new();
+        let heap = heap(&mut mem, 12);
+        let base = mem.as_mut_ptr();
+        unsafe {
+            let a = heap.take(4, UNIT);
+            let b = heap.take(4, UNIT);
+            let c = heap.take(4, UNIT);
+            heap.insert(a);
+            heap.insert(c);
+            assert_eq!(free_blocks(&heap, base), [(0, 4), (8, 4)]);
+            heap.insert(b);
+            assert_eq!(free_blocks(&heap, base), [(0, 12)]);
+        }
+    }
+
+    #[test]
+    fn take_aligns_payload() {
+        let mut mem = Vec::new();
+        let heap = heap(&mut mem, 64);
+        let base = mem.as_mut_ptr();
+        let align = 8 * UNIT;
+        unsafe {
+            let blk = heap.take(3, align);
+            assert!(!blk.is_null());
+            assert_eq!(blk.add(1) as usize % align, 0);
+            // The skipped front of the block stays free.
+            let front = blk.offset_from(base) as usize;
+            let blocks = free_blocks(&heap, base);
+            if front > 0 {
+                assert_eq!(blocks[0], (0, front));
+            }
+            assert_eq!(*blocks.last().unwrap(), (front + 3, 64 - front - 3));
+            heap.insert(blk);
+            assert_eq!(free_blocks(&heap, base), [(0, 64)]);
+        }
+    }
+}
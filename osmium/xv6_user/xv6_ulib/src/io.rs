@@ -0,0 +1,516 @@
+//! Reading and writing file descriptors, the standard streams, and buffered line reading.
+//!
+//! The `Read` and `Write` traits are implemented by everything that can be read or written: files
+//! and pipes (`fs::File`), borrowed descriptors (`Fd`) and the standard streams.
+//!
+//! Output written through `stdout()` and `stderr()` goes through a per-stream buffer whose
+//! behaviour is selected with `BufferMode`. Standard output is line buffered and standard error is
+//! unbuffered by default. Buffers are flushed by `exit`, so output isn't lost when a program
+//! returns from main or exits early.
+//!
+//! Input is read through the `BufRead` trait, implemented by `BufReader` for any `Read` and by
+//! `Stdin` for standard input.
+
+use crate::error::{Error, Result};
+use crate::syscall;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cell::UnsafeCell;
+use core::fmt;
+
+/// Size of the buffers used by the standard streams and `BufReader`.
+pub const BUFSIZE: usize = 512;
+
+/// Source of bytes.
+pub trait Read {
+    /// Reads data into `buf`. Returns the number of bytes read, or 0 at the end of the input.
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize>;
+
+    /// Fills all of `buf`.
+    ///
+    /// # Errors
+    /// Returns `Error::UnexpectedEof` if the input ends first. The contents of `buf` are
+    /// unspecified in that case.
+    fn read_exact(&mut self, mut buf: &mut [u8]) -> Result<()> {
+        while !buf.is_empty() {
+            match self.read(buf)? {
+                0 => return Err(Error::UnexpectedEof),
+                n => buf = &mut buf[n..],
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads everything until the end of the input, appending it to `buf`. Returns the number of
+    /// bytes read.
+    fn read_to_end(&mut self, buf: &mut Vec<u8>) -> Result<usize> {
+        let mut chunk = [0; BUFSIZE];
+        let mut total = 0;
+        loop {
+            match self.read(&mut chunk)? {
+                0 => return Ok(total),
+                n => {
+                    buf.extend_from_slice(&chunk[..n]);
+                    total += n;
+                }
+            }
+        }
+    }
+
+    /// Reads everything until the end of the input, appending it to `buf`. Returns the number of
+    /// bytes read. Bytes that aren't valid UTF-8 are replaced with U+FFFD.
+    fn read_to_string(&mut self, buf: &mut String) -> Result<usize> {
+        let mut bytes = Vec::new();
+        let n = self.read_to_end(&mut bytes)?;
+        buf.push_str(&String::from_utf8_lossy(&bytes));
+        Ok(n)
+    }
+}
+
+/// Sink for bytes.
+pub trait Write {
+    /// Writes some of `buf`. Returns the number of bytes written.
+    fn write(&mut self, buf: &[u8]) -> Result<usize>;
+
+    /// Writes any buffered data to the underlying file descriptor.
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Writes all of `buf`, retrying on short writes.
+    ///
+    /// # Errors
+    /// Returns `Error::Io` if the file stops accepting data.
+    fn write_all(&mut self, mut buf: &[u8]) -> Result<()> {
+        while !buf.is_empty() {
+            match self.write(buf)? {
+                0 => return Err(Error::Io),
+                n => buf = &buf[n..],
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes formatted output. This lets the `write!` and `writeln!` macros be used with any
+    /// `Write`.
+    fn write_fmt(&mut self, args: fmt::Arguments) -> Result<()> {
+        // Adapter that remembers the underlying error, since `fmt::Error` carries no information.
+        struct Adapter<'a, W: ?Sized> {
+            inner: &'a mut W,
+            error: Option<Error>,
+        }
+
+        impl<W: Write + ?Sized> fmt::Write for Adapter<'_, W> {
+            fn write_str(&mut self, s: &str) -> fmt::Result {
+                self.inner.write_all(s.as_bytes()).map_err(|e| {
+                    self.error = Some(e);
+                    fmt::Error
+                })
+            }
+        }
+
+        let mut adapter = Adapter {
+            inner: self,
+            error: None,
+        };
+        match fmt::write(&mut adapter, args) {
+            Ok(()) => Ok(()),
+            Err(_) => Err(adapter.error.unwrap_or(Error::Io)),
+        }
+    }
+}
+
+impl<R: Read + ?Sized> Read for &mut R {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        (**self).read(buf)
+    }
+}
+
+impl<W: Write + ?Sized> Write for &mut W {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        (**self).write(buf)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        (**self).flush()
+    }
+}
+
+/// A file descriptor that is borrowed rather than owned: it isn't closed on drop. Useful for
+/// descriptors inherited from the parent, like the console, or owned by someone else.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Fd(pub i32);
+
+impl Read for Fd {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        syscall::read(self.0, buf)
+    }
+}
+
+impl Write for Fd {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        syscall::write(self.0, buf)
+    }
+}
+
+/// When buffered data is written to the underlying file descriptor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BufferMode {
+    /// Every write goes straight to the file descriptor. For input, data is read one byte at a
+    /// time so nothing past the current line is consumed.
+    Unbuffered,
+    /// Output is flushed after every write containing a newline, and when the buffer fills up.
+    Line,
+    /// Output is only flushed when the buffer fills up or on an explicit `flush`.
+    Full,
+}
+
+/// Storage for the standard streams. User programs are single threaded, so the contents are
+/// never accessed concurrently; access goes through `with` so no two mutable references to the
+/// contents can be live at once.
+struct Global<T>(UnsafeCell<T>);
+
+unsafe impl<T> Sync for Global<T> {}
+
+impl<T> Global<T> {
+    const fn new(value: T) -> Self {
+        Global(UnsafeCell::new(value))
+    }
+
+    fn with<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        unsafe { f(&mut *self.0.get()) }
+    }
+}
+
+/// Buffered output stream.
+struct Output {
+    fd: i32,
+    mode: BufferMode,
+    buf: [u8; BUFSIZE],
+    len: usize,
+}
+
+impl Output {
+    const fn new(fd: i32, mode: BufferMode) -> Self {
+        Output {
+            fd,
+            mode,
+            buf: [0; BUFSIZE],
+            len: 0,
+        }
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        if self.mode == BufferMode::Unbuffered {
+            self.flush()?;
+            return Fd(self.fd).write_all(data);
+        }
+        if self.len + data.len() > BUFSIZE {
+            self.flush()?;
+        }
+        if data.len() >= BUFSIZE {
+            // Too large to buffer; the buffer is empty now, so ordering is preserved.
+            return Fd(self.fd).write_all(data);
+        }
+        self.buf[self.len..self.len + data.len()].copy_from_slice(data);
+        self.len += data.len();
+        if self.mode == BufferMode::Line && data.contains(&b'\n') {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        let len = self.len;
+        self.len = 0;
+        Fd(self.fd).write_all(&self.buf[..len])
+    }
+
+    fn set_mode(&mut self, mode: BufferMode) -> Result<()> {
+        self.mode = mode;
+        self.flush()
+    }
+}
+
+static STDOUT: Global<Output> = Global::new(Output::new(1, BufferMode::Line));
+static STDERR: Global<Output> = Global::new(Output::new(2, BufferMode::Unbuffered));
+static STDIN: Global<BufReader<Fd>> = Global::new(BufReader::new(Fd(0)));
+
+/// Flushes standard output and standard error. Errors are ignored since there is nowhere left to
+/// report them.
+pub fn flush_all() {
+    let _ = STDOUT.with(|out| out.flush());
+    let _ = STDERR.with(|out| out.flush());
+}
+
+macro_rules! output_handle {
+    ($(#[$doc:meta])* $name:ident, $global:ident, $ctor:ident) => {
+        $(#[$doc])*
+        pub struct $name {
+            _private: (),
+        }
+
+        impl $name {
+            /// Returns the stream's current buffering behaviour.
+            pub fn buffer_mode(&self) -> BufferMode {
+                $global.with(|out| out.mode)
+            }
+
+            /// Changes the stream's buffering behaviour. Any data already buffered is flushed.
+            pub fn set_buffer_mode(&mut self, mode: BufferMode) -> Result<()> {
+                $global.with(|out| out.set_mode(mode))
+            }
+        }
+
+        impl Write for $name {
+            fn write(&mut self, buf: &[u8]) -> Result<usize> {
+                $global.with(|out| out.write(buf))?;
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> Result<()> {
+                $global.with(|out| out.flush())
+            }
+        }
+
+        /// Returns a handle to the stream, suitable for writing with the `write!` macro.
+        pub fn $ctor() -> $name {
+            $name { _private: () }
+        }
+    };
+}
+
+output_handle!(
+    /// Handle to the process's standard output, file descriptor 1. Line buffered by default.
+    Stdout,
+    STDOUT,
+    stdout
+);
+
+output_handle!(
+    /// Handle to the process's standard error, file descriptor 2. Unbuffered by default.
+    Stderr,
+    STDERR,
+    stderr
+);
+
+/// Reading of delimited records, such as lines, from a buffered source.
+pub trait BufRead {
+    /// Reads bytes into `buf` until `delim` or the end of the input is reached. The delimiter is
+    /// included in `buf` if found. Returns the number of bytes read, or 0 at the end of the input.
+    fn read_until(&mut self, delim: u8, buf: &mut Vec<u8>) -> Result<usize>;
+
+    /// Reads a line into `buf`, including the trailing newline if there is one. Returns the number
+    /// of bytes read, or 0 at the end of the input. Bytes that aren't valid UTF-8 are replaced with
+    /// U+FFFD.
+    fn read_line(&mut self, buf: &mut String) -> Result<usize> {
+        let mut bytes = Vec::new();
+        let n = self.read_until(b'\n', &mut bytes)?;
+        buf.push_str(&String::from_utf8_lossy(&bytes));
+        Ok(n)
+    }
+
+    /// Returns an iterator over the lines of the input, without their trailing newlines.
+    fn lines(self) -> Lines<Self>
+    where
+        Self: Sized,
+    {
+        Lines { reader: self }
+    }
+}
+
+/// Iterator over the lines of a `BufRead`, created by `BufRead::lines`.
+pub struct Lines<B> {
+    reader: B,
+}
+
+impl<B: BufRead> Iterator for Lines<B> {
+    type Item = Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut line = String::new();
+        match self.reader.read_line(&mut line) {
+            Ok(0) => None,
+            Ok(_) => {
+                if line.ends_with('\n') {
+                    line.pop();
+                }
+                Some(Ok(line))
+            }
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+/// Buffered reader over any `Read`, such as a `File` or an `Fd`.
+pub struct BufReader<R> {
+    inner: R,
+    buf: [u8; BUFSIZE],
+    /// Maximum number of bytes requested from `inner` at once.
+    capacity: usize,
+    pos: usize,
+    filled: usize,
+}
+
+impl<R: Read> BufReader<R> {
+    /// Returns a reader over `inner` that reads up to `BUFSIZE` bytes at a time.
+    pub const fn new(inner: R) -> Self {
+        Self::with_capacity(inner, BUFSIZE)
+    }
+
+    /// Returns a reader over `inner` that reads up to `capacity` bytes at a time. A capacity of 1
+    /// never consumes input past the requested delimiter, which matters when the file descriptor
+    /// is shared with a child process. `capacity` is clamped to `1..=BUFSIZE`.
+    pub const fn with_capacity(inner: R, capacity: usize) -> Self {
+        let capacity = if capacity == 0 {
+            1
+        } else if capacity > BUFSIZE {
+            BUFSIZE
+        } else {
+            capacity
+        };
+        BufReader {
+            inner,
+            buf: [0; BUFSIZE],
+            capacity,
+            pos: 0,
+            filled: 0,
+        }
+    }
+
+    /// Returns a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Returns the underlying reader. Any buffered data is lost.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for BufReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        if self.pos == self.filled {
+            return self.inner.read(buf);
+        }
+        let available = &self.buf[self.pos..self.filled];
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+impl<R: Read> BufRead for BufReader<R> {
+    fn read_until(&mut self, delim: u8, buf: &mut Vec<u8>) -> Result<usize> {
+        let mut total = 0;
+        loop {
+            if self.pos == self.filled {
+                self.filled = self.inner.read(&mut self.buf[..self.capacity])?;
+                self.pos = 0;
+                if self.filled == 0 {
+                    return Ok(total);
+                }
+            }
+            let available = &self.buf[self.pos..self.filled];
+            match available.iter().position(|&b| b == delim) {
+                Some(i) => {
+                    buf.extend_from_slice(&available[..=i]);
+                    self.pos += i + 1;
+                    return Ok(total + i + 1);
+                }
+                None => {
+                    buf.extend_from_slice(available);
+                    total += available.len();
+                    self.pos = self.filled;
+                }
+            }
+        }
+    }
+}
+
+/// Handle to the process's standard input, file descriptor 0. Input is buffered in a reader shared
+/// by all handles, so no data is lost between calls.
+pub struct Stdin {
+    _private: (),
+}
+
+impl Stdin {
+    /// Changes how standard input is read. `BufferMode::Unbuffered` reads one byte at a time, so a
+    /// child process that inherits standard input sees everything after the last line read.
+    /// Input already buffered is kept.
+    pub fn set_buffer_mode(&mut self, mode: BufferMode) {
+        STDIN.with(|reader| {
+            reader.capacity = match mode {
+                BufferMode::Unbuffered => 1,
+                BufferMode::Line | BufferMode::Full => BUFSIZE,
+            }
+        });
+    }
+}
+
+impl Read for Stdin {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        STDIN.with(|reader| reader.read(buf))
+    }
+}
+
+impl BufRead for Stdin {
+    fn read_until(&mut self, delim: u8, buf: &mut Vec<u8>) -> Result<usize> {
+        STDIN.with(|reader| reader.read_until(delim, buf))
+    }
+}
+
+/// Returns a handle to standard input.
+pub fn stdin() -> Stdin {
+    Stdin { _private: () }
+}
+
+#[doc(hidden)]
+pub fn _print(args: fmt::Arguments) {
+    let _ = stdout().write_fmt(args);
+}
+
+#[doc(hidden)]
+pub fn _eprint(args: fmt::Arguments) {
+    let _ = stderr().write_fmt(args);
+}
+
+/// Prints to standard output.
+#[macro_export]
+macro_rules! print {
+    ($($arg:tt)*) => {
+        $crate::io::_print(format_args!($($arg)*))
+    };
+}
+
+/// Prints to standard output, followed by a newline.
+#[macro_export]
+macro_rules! println {
+    () => {
+        $crate::print!("\n")
+    };
+    ($($arg:tt)*) => {
+        $crate::io::_print(format_args!("{}\n", format_args!($($arg)*)))
+    };
+}
+
+/// Prints to standard error.
+#[macro_export]
+macro_rules! eprint {
+    ($($arg:tt)*) => {
+        $crate::io::_eprint(format_args!($($arg)*))
+    };
+}
+
+/// Prints to standard error, followed by a newline.
+#[macro_export]
+macro_rules! eprintln {
+    () => {
+        $crate::eprint!("\n")
+    };
+    ($($arg:tt)*) => {
+        $crate::io::_eprint(format_args!("{}\n", format_args!($($arg)*)))
+    };
+}
@@ -1,6 +1,6 @@
 //! # xv6_ulib
-//! Library for xv6 user programs. Contains FFI bindings for xv6's C user library and native Rust
-//! functions.
+//! Library for xv6 user programs. System calls are issued directly with `ecall`, so programs don't
+//! need to link against xv6's C user library.
 //!
 //! To create a new user program `<uprog_name>` in Rust,
 //! 1. Run `cargo new --lib <uprog_name>` in the directory `rustcomps/xv6_uprogs`.
@@ -11,21 +11,43 @@
 //!    top of the file to bring useful types and functions into scope.
 //! ```
 //! use xv6_defs::c_types::*;
-//! use xv6_ulib::c_user::*;
+//! use xv6_ulib::*;
 //! ```
-//! 5. Copy the main function definition from an existing user program (like `echo`) and replace it
-//!    with your code.
+//! 5. Write a main function taking `Args` and returning the exit status, and register it with
+//!    `xv6_ulib::entry!(main_fn)` like `echo` does. The library provides the `_start` entry point
+//!    and calls `exit` with the returned status.
 //! 6. Add the new user program to the `RUST_UPROGS` variable in `Makefile` and remove it from the
 //!    `UPROGS` variable.
+//!
+//! User programs have a heap (see `heap`), so they can use `Vec`, `String` and `Box` by adding
+//! `extern crate alloc;` to the top of the file.
+
+#![cfg_attr(not(test), no_std)]
+
+extern crate alloc;
+
+pub mod argparse;
+mod ecall;
+pub mod error;
+pub mod fs;
+pub mod heap;
+pub mod io;
+mod panic;
+pub mod path;
+pub mod process;
+mod rt;
+pub mod stat;
+pub mod syscall;
+
+pub use error::{Error, Result};
+pub use fs::{read_dir, File};
+pub use io::{stderr, stdin, stdout};
+pub use panic::PANIC_STATUS;
+pub use path::PathBuf;
+pub use process::Command;
+pub use stat::{FileType, Stat};
+pub use syscall::*;
 
-#![no_std]
-
-extern crate panic_halt;
-
-pub mod c_user;
-
-use core::fmt;
-use core::fmt::Write;
 use core::slice;
 use core::str;
 use xv6_defs::c_types::*;
@@ -38,16 +60,33 @@ pub struct CStr<'a> {
 impl<'a> CStr<'a> {
     /// Constructs a new `CStr` and checks its validity. Verifies it contains only ASCII characters
     /// and it has exactly one null terminator as the last bytes. Exits the program if an error is
-    /// detected.
+    /// detected; use `try_new` to handle the error instead.
     pub fn new(data: &'a str) -> CStr<'a> {
-        let len = data.len();
-        if data.is_empty()
-            || !data.is_ascii()
-            || !data.ends_with('\0')
-            || data[0..len - 1].contains('\0')
-        {
-            exit(1);
+        match CStr::try_new(data) {
+            Ok(s) => s,
+            Err(_) => exit(1),
+        }
+    }
+
+    /// Constructs a new `CStr`, checking it the same way as `new`.
+    ///
+    /// # Errors
+    /// Returns `Error::InvalidArgument` if `data` isn't ASCII or isn't terminated by exactly one
+    /// null byte at the end.
+    pub fn try_new(data: &'a str) -> Result<CStr<'a>> {
+        if path::is_valid_c_str(data.as_bytes()) {
+            Ok(CStr { data })
+        } else {
+            Err(Error::InvalidArgument)
         }
+    }
+
+    /// Constructs a new `CStr` without checking it. Used by `cstr!`, which checks literals at
+    /// compile time.
+    ///
+    /// # Safety
+    /// `data` must be ASCII and end with its only null byte.
+    pub const unsafe fn new_unchecked(data: &'a str) -> CStr<'a> {
         CStr { data }
     }
 
@@ -74,74 +113,10 @@ impl<'a> CStr<'a> {
     pub fn as_str(&self) -> &str {
         self.data
     }
-}
 
-/// Terminates the program with exit code `code`.
-pub fn exit(code: i32) -> ! {
-    unsafe {
-        c_user::exit(code);
-    }
-    loop {}
-}
-
-/// Writes the data in `buf` to the file descriptor `fildes`. Returns the number of bytes written.
-///
-/// # Errors
-/// Returns an error if the system call failed.
-pub fn write(fildes: i32, buf: &[u8]) -> Result<usize, ()> {
-    let result;
-    unsafe {
-        result = c_user::write(fildes, buf.as_ptr(), buf.len() as c_int);
-    }
-    if result >= 0 {
-        Ok(result as usize)
-    } else {
-        Err(())
-    }
-}
-
-/// Reads the data in `buf` from the file descriptor `fildes`. Returns the number of bytes read.
-///
-/// # Errors
-/// Returns an error if the system call failed.
-pub fn read(fildes: i32, buf: &[u8]) -> Result<usize, ()> {
-    let result;
-    unsafe {
-        result = c_user::read(fildes, buf.as_ptr(), buf.len() as c_int);
-    }
-    if result >= 0 {
-        Ok(result as usize)
-    } else {
-        Err(())
-    }
-}
-
-/// Release open file descriptor `fildes`. Returns true if successful, else false.
-pub fn close(fildes: i32) -> Result<(), ()> {
-    let result: i32;
-    unsafe {
-        result = c_user::close(fildes);
-    }
-    if result >= 0 {
-        Ok(())
-    } else {
-        Err(())
-    }
-}
-
-/// Opens `file` with corresponding read/write `flags`. Returns a file descriptor.
-///
-/// # Errors
-/// Returns an error if the system call failed.
-pub fn open(file: &[u8], flags: i32) -> Result<i32, ()> {
-    let result: i32;
-    unsafe {
-        result = c_user::open(file.as_ptr(), flags);
-    }
-    if result >= 0 {
-        Ok(result)
-    } else {
-        Err(())
+    /// Returns the string excluding the null terminator.
+    pub fn chars_as_str(&self) -> &str {
+        &self.data[0..self.len()]
     }
 }
 
@@ -182,8 +157,8 @@ impl Iterator for Args {
             let as_str;
             unsafe {
                 let arg = *(self.args.add(self.current));
-                let len = c_user::strlen(arg);
-                let bytes = slice::from_raw_parts(arg, (len + 1) as usize);
+                let len = strlen(arg);
+                let bytes = slice::from_raw_parts(arg, len + 1);
                 as_str = str::from_utf8(bytes).unwrap();
             }
             self.current += 1;
@@ -192,40 +167,64 @@ impl Iterator for Args {
     }
 }
 
-/// Given a safe main function `main`, executes the function passing in the arguments represented
-/// by `argc` and `argv` wrapped in a safe `Args` type. This function calls `exit` with the exit
-/// status that the main function returns, so there's no need to call it at the end of the main
-/// function.
+/// Returns the length of the null terminated string `s`, not including the terminator.
 ///
 /// # Safety
-/// The `argv` pointer must refer to a valid array of C strings of length `argc`.
-pub unsafe fn run_prog(
-    argc: c_int,
-    argv: *const *const c_char,
-    main: impl FnOnce(Args) -> i32,
-) -> c_int {
-    exit(main(Args::new(argc, argv)));
-}
-
-/// Dummy structure representing a `core::fmt::Write` implementation that can write to stdout.
-struct StdOut;
-
-impl Write for StdOut {
-    fn write_str(&mut self, s: &str) -> fmt::Result {
-        let bytes = s.as_bytes();
-        let len = bytes.len();
-        let mut num_written = 0;
-        while num_written < len {
-            match write(1, &bytes[num_written..len]) {
-                Ok(n) => num_written += n,
-                Err(_) => return Err(core::fmt::Error),
-            }
-        }
-        Ok(())
+/// `s` must point to a null terminated string.
+unsafe fn strlen(s: *const c_char) -> usize {
+    let mut len = 0;
+    while *s.add(len) != 0 {
+        len += 1;
     }
+    len
 }
 
-/// Returns a structure suitable for writing to stdout with the `write!` macro.
-pub fn stdout() -> impl Write {
-    StdOut
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec::Vec;
+
+    #[test]
+    fn cstr_validation() {
+        assert!(CStr::try_new("abc\0").is_ok());
+        assert!(CStr::try_new("\0").unwrap().is_empty());
+        assert_eq!(CStr::try_new("").err(), Some(Error::InvalidArgument));
+        assert_eq!(CStr::try_new("abc").err(), Some(Error::InvalidArgument));
+        assert_eq!(CStr::try_new("a\0c\0").err(), Some(Error::InvalidArgument));
+        assert_eq!(CStr::try_new("abc\0\0").err(), Some(Error::InvalidArgument));
+        assert_eq!(CStr::try_new("é\0").err(), Some(Error::InvalidArgument));
+    }
+
+    #[test]
+    fn cstr_accessors() {
+        let s = cstr!("hello");
+        assert_eq!(s.len(), 5);
+        assert_eq!(s.as_bytes(), b"hello\0");
+        assert_eq!(s.chars_as_bytes(), b"hello");
+        assert_eq!(s.as_str(), "hello\0");
+        assert_eq!(s.chars_as_str(), "hello");
+    }
+
+    #[test]
+    fn args_iteration() {
+        let strs: [&[u8]; 3] = [b"echo\0", b"\0", b"two words\0"];
+        let argv: Vec<*const c_char> = strs.iter().map(|s| s.as_ptr() as *const c_char).collect();
+        let args = unsafe { Args::new(argv.len() as c_int, argv.as_ptr()) };
+        assert_eq!(args.argc(), 3);
+        let args: Vec<&str> = args.map(|arg| arg.data.trim_end_matches('\0')).collect();
+        assert_eq!(args, ["echo", "", "two words"]);
+    }
+
+    #[test]
+    fn host_syscalls_fail() {
+        assert_eq!(syscall::close(0), Err(Error::Unsupported));
+        assert_eq!(syscall::fork(), Err(Error::Unsupported));
+    }
+
+    #[test]
+    fn args_empty() {
+        let mut args = unsafe { Args::new(0, core::ptr::null()) };
+        assert_eq!(args.argc(), 0);
+        assert!(args.next().is_none());
+    }
 }
@@ -0,0 +1,45 @@
+//! Panic handler for user programs.
+//!
+//! A panic prints its message and location to standard error and exits with `PANIC_STATUS`, so a
+//! failed `unwrap()` shows up as an error instead of hanging the program.
+//!
+//! Host unit tests use the standard library's panic handler instead.
+
+#![cfg_attr(test, allow(dead_code, unused_imports))]
+
+use crate::ecall::syscall1;
+use crate::io::{Fd, Write};
+use crate::syscall::exit;
+use core::panic::PanicInfo;
+use core::sync::atomic::{AtomicBool, Ordering};
+use xv6_defs::syscall::SYS_exit;
+
+/// Exit status of a program that panicked. The same as Rust programs on other systems.
+pub const PANIC_STATUS: i32 = 101;
+
+/// Set while handling a panic, to detect a panic while printing the message or flushing output.
+static PANICKING: AtomicBool = AtomicBool::new(false);
+
+#[cfg_attr(not(test), panic_handler)]
+fn panic(info: &PanicInfo) -> ! {
+    if PANICKING.swap(true, Ordering::Relaxed) {
+        // Panicked again, probably in the standard streams. Exit without touching them.
+        unsafe {
+            syscall1(SYS_exit, PANIC_STATUS as usize);
+        }
+        loop {}
+    }
+    // Write directly to fd 2 rather than through `stderr()`, which may be what panicked.
+    let mut err = Fd(2);
+    let _ = match info.location() {
+        Some(loc) => writeln!(
+            err,
+            "panic: {} at {}:{}",
+            info.message(),
+            loc.file(),
+            loc.line()
+        ),
+        None => writeln!(err, "panic: {}", info.message()),
+    };
+    exit(PANIC_STATUS)
+}
@@ -0,0 +1,350 @@
+//! Owned paths and C strings.
+//!
+//! `PathBuf` holds a null terminated path in a fixed buffer of `MAXPATH` bytes, the longest path
+//! the kernel accepts (`kernel/param.h`), so building paths at runtime needs no heap and can't
+//! produce a path the kernel would reject. Every operation that would make the path too long or
+//! invalid returns an error instead.
+//!
+//! The `cstr!` macro turns a string literal into a `CStr`, checking it at compile time.
+
+use crate::error::{Error, Result};
+use crate::CStr;
+use core::fmt;
+use core::str;
+use xv6_defs::param::MAXPATH;
+
+/// Returns whether `bytes` is ASCII and ends with its only null byte. Used by `CStr::try_new` and,
+/// at compile time, by `cstr!`.
+#[doc(hidden)]
+pub const fn is_valid_c_str(bytes: &[u8]) -> bool {
+    if bytes.is_empty() || bytes[bytes.len() - 1] != 0 {
+        return false;
+    }
+    let mut i = 0;
+    while i < bytes.len() - 1 {
+        if bytes[i] == 0 || !bytes[i].is_ascii() {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// Returns a `CStr` for a string literal, adding the null terminator. Literals that aren't ASCII
+/// or contain a null byte are rejected at compile time.
+///
+/// ```ignore
+/// let fd = open(&cstr!("console"), O_RDWR)?;
+/// ```
+#[macro_export]
+macro_rules! cstr {
+    ($s:expr) => {{
+        const S: &str = concat!($s, "\0");
+        const _: () = assert!(
+            $crate::path::is_valid_c_str(S.as_bytes()),
+            "cstr! literal must be ASCII without null bytes"
+        );
+        unsafe { $crate::CStr::new_unchecked(S) }
+    }};
+}
+
+/// An owned, null terminated path of at most `MAXPATH - 1` characters.
+#[derive(Clone)]
+pub struct PathBuf {
+    buf: [u8; MAXPATH],
+    /// Length of the path, not including the null terminator.
+    len: usize,
+}
+
+impl PathBuf {
+    /// Returns an empty path.
+    pub const fn new() -> PathBuf {
+        PathBuf {
+            buf: [0; MAXPATH],
+            len: 0,
+        }
+    }
+
+    /// Returns a path holding `s`, which must not include a null terminator.
+    ///
+    /// # Errors
+    /// Returns `Error::NameTooLong` if `s` doesn't fit, or `Error::InvalidArgument` if it isn't
+    /// ASCII or contains a null byte.
+    pub fn try_from_str(s: &str) -> Result<PathBuf> {
+        let mut path = PathBuf::new();
+        path.append(s)?;
+        Ok(path)
+    }
+
+    /// Returns the path without the null terminator.
+    pub fn as_str(&self) -> &str {
+        // Only ASCII is ever copied into the buffer.
+        unsafe { str::from_utf8_unchecked(&self.buf[..self.len]) }
+    }
+
+    /// Returns the path as a `CStr` for passing to system calls.
+    pub fn as_c_str(&self) -> CStr<'_> {
+        unsafe { CStr::new_unchecked(str::from_utf8_unchecked(&self.buf[..=self.len])) }
+    }
+
+    /// Returns the length of the path, not including the null terminator.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether the path is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns whether the path starts at the root directory.
+    pub fn is_absolute(&self) -> bool {
+        self.as_str().starts_with('/')
+    }
+
+    /// Adds `s` to the end of the path as is. On error the path is left unchanged.
+    fn append(&mut self, s: &str) -> Result<()> {
+        if !s.is_ascii() || s.contains('\0') {
+            return Err(Error::InvalidArgument);
+        }
+        // Leave room for the null terminator.
+        if self.len + s.len() >= MAXPATH {
+            return Err(Error::NameTooLong);
+        }
+        self.buf[self.len..self.len + s.len()].copy_from_slice(s.as_bytes());
+        self.len += s.len();
+        self.buf[self.len] = 0;
+        Ok(())
+    }
+
+    /// Extends the path with `s`, adding a `/` between them if needed. If `s` is absolute it
+    /// replaces the path. On error the path is left unchanged.
+    ///
+    /// # Errors
+    /// Returns `Error::NameTooLong` if the result doesn't fit, or `Error::InvalidArgument` if `s`
+    /// isn't ASCII or contains a null byte.
+    pub fn push(&mut self, s: &str) -> Result<()> {
+        if s.starts_with('/') {
+            *self = PathBuf::try_from_str(s)?;
+            return Ok(());
+        }
+        let len = self.len;
+        if len > 0 && !self.as_str().ends_with('/') {
+            self.append("/")?;
+        }
+        self.append(s).inspect_err(|_| self.truncate(len))
+    }
+
+    /// Returns a new path with `s` joined onto this one, as with `push`.
+    pub fn join(&self, s: &str) -> Result<PathBuf> {
+        let mut path = self.clone();
+        path.push(s)?;
+        Ok(path)
+    }
+
+    /// Truncates the path to its parent. Returns false, leaving the path unchanged, if it has no
+    /// parent.
+    pub fn pop(&mut self) -> bool {
+        match self.parent().map(str::len) {
+            Some(len) => {
+                self.truncate(len);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn truncate(&mut self, len: usize) {
+        self.len = len;
+        self.buf[len] = 0;
+    }
+
+    /// Returns the path without its final component, or `None` if the path is empty or the root.
+    /// The parent of a relative path with one component is the empty path.
+    ///
+    /// `"/a/b"` and `"/a/b/"` have parent `"/a"`, `"/a"` has parent `"/"`, and `"a"` has parent `""`.
+    pub fn parent(&self) -> Option<&str> {
+        let path = self.as_str().trim_end_matches('/');
+        if path.is_empty() {
+            return None;
+        }
+        match path.rfind('/') {
+            Some(i) => {
+                let parent = path[..i].trim_end_matches('/');
+                if parent.is_empty() {
+                    Some("/")
+                } else {
+                    Some(parent)
+                }
+            }
+            None => Some(""),
+        }
+    }
+
+    /// Returns the final component of the path, or `None` if it is empty or the root.
+    pub fn file_name(&self) -> Option<&str> {
+        self.components().next_back()
+    }
+
+    /// Returns an iterator over the components of the path. Repeated and trailing slashes are
+    /// ignored, and the root directory is not a component.
+    pub fn components(&self) -> Components<'_> {
+        Components {
+            rest: self.as_str(),
+        }
+    }
+}
+
+impl Default for PathBuf {
+    fn default() -> Self {
+        PathBuf::new()
+    }
+}
+
+impl PartialEq for PathBuf {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for PathBuf {}
+
+impl fmt::Debug for PathBuf {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for PathBuf {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Iterator over the components of a path, returned by `PathBuf::components`.
+pub struct Components<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Iterator for Components<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        self.rest = self.rest.trim_start_matches('/');
+        if self.rest.is_empty() {
+            return None;
+        }
+        let end = self.rest.find('/').unwrap_or(self.rest.len());
+        let (component, rest) = self.rest.split_at(end);
+        self.rest = rest;
+        Some(component)
+    }
+}
+
+impl<'a> DoubleEndedIterator for Components<'a> {
+    fn next_back(&mut self) -> Option<&'a str> {
+        self.rest = self.rest.trim_end_matches('/');
+        if self.rest.is_empty() {
+            return None;
+        }
+        let start = self.rest.rfind('/').map_or(0, |i| i + 1);
+        let (rest, component) = self.rest.split_at(start);
+        self.rest = rest;
+        Some(component)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::string::String;
+    use std::vec::Vec;
+
+    fn path(s: &str) -> PathBuf {
+        PathBuf::try_from_str(s).unwrap()
+    }
+
+    fn components(path: &PathBuf) -> Vec<&str> {
+        path.components().collect()
+    }
+
+    #[test]
+    fn root() {
+        let mut root = path("/");
+        assert!(root.is_absolute());
+        assert_eq!(root.parent(), None);
+        assert_eq!(root.file_name(), None);
+        assert!(components(&root).is_empty());
+        assert!(!root.pop());
+        assert_eq!(root.as_str(), "/");
+    }
+
+    #[test]
+    fn single_component() {
+        let mut a = path("a");
+        assert!(!a.is_absolute());
+        assert_eq!(a.parent(), Some(""));
+        assert_eq!(a.file_name(), Some("a"));
+        assert_eq!(components(&a), ["a"]);
+        assert!(a.pop());
+        assert!(a.is_empty());
+        assert!(!a.pop());
+    }
+
+    #[test]
+    fn repeated_and_trailing_slashes() {
+        let mut p = path("a//b/");
+        assert_eq!(components(&p), ["a", "b"]);
+        assert_eq!(p.components().rev().collect::<Vec<_>>(), ["b", "a"]);
+        assert_eq!(p.file_name(), Some("b"));
+        assert_eq!(p.parent(), Some("a"));
+        assert!(p.pop());
+        assert_eq!(p.as_str(), "a");
+        assert_eq!(path("/a/b").parent(), Some("/a"));
+        assert_eq!(path("/a").parent(), Some("/"));
+    }
+
+    #[test]
+    fn push_and_join() {
+        let mut p = PathBuf::new();
+        p.push("a").unwrap();
+        assert_eq!(p.as_str(), "a");
+        p.push("b").unwrap();
+        assert_eq!(p.as_str(), "a/b");
+        assert_eq!(path("a/").join("b").unwrap().as_str(), "a/b");
+        assert_eq!(p.as_c_str().as_bytes(), b"a/b\0");
+
+        // An absolute path replaces the whole path.
+        p.push("/etc").unwrap();
+        assert_eq!(p.as_str(), "/etc");
+        assert_eq!(p.join("/").unwrap().as_str(), "/");
+
+        assert_eq!(p.push("x\0"), Err(Error::InvalidArgument));
+        assert_eq!(p.push("é"), Err(Error::InvalidArgument));
+        assert_eq!(p.as_str(), "/etc");
+    }
+
+    #[test]
+    fn maxpath_limit() {
+        let longest: String = "a".repeat(MAXPATH - 1);
+        assert_eq!(path(&longest).len(), MAXPATH - 1);
+        assert_eq!(
+            PathBuf::try_from_str(&"a".repeat(MAXPATH)).err(),
+            Some(Error::NameTooLong)
+        );
+
+        // Room for the separator but not the component: the separator mustn't stay behind.
+        let mut p = path(&"a".repeat(MAXPATH - 2));
+        assert_eq!(p.push("b"), Err(Error::NameTooLong));
+        assert_eq!(p.len(), MAXPATH - 2);
+        assert_eq!(p.as_c_str().as_bytes().len(), MAXPATH - 1);
+
+        let mut p = path("a");
+        assert_eq!(p.push(&longest[..MAXPATH - 2]), Err(Error::NameTooLong));
+        assert_eq!(
+            p.push(&std::format!("/{}", longest)),
+            Err(Error::NameTooLong)
+        );
+        assert_eq!(p.as_str(), "a");
+    }
+}
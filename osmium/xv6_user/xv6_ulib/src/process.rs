@@ -0,0 +1,301 @@
+//! Creating and managing processes.
+//!
+//! `fork`, `exec` and `wait` are thin wrappers over the system calls. `Command` builds on them to
+//! spawn a program with its standard streams inherited, redirected to a file, or connected to the
+//! parent through a pipe, the same way `user/sh.c` sets up redirections with `close` and `dup`.
+
+use crate::error::{Error, Result};
+use crate::fs::{self, File};
+use crate::io::{self, Read};
+use crate::syscall;
+use crate::CStr;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+pub use crate::syscall::{exec, exit, getpid, kill, wait};
+
+/// Which process `fork` returned in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ForkResult {
+    /// Returned in the parent, with the child's PID.
+    Parent(i32),
+    /// Returned in the child.
+    Child,
+}
+
+/// Creates a new process that is a copy of the caller. Buffered standard output and standard error
+/// are flushed first so the child doesn't inherit, and later repeat, the parent's pending output.
+///
+/// # Errors
+/// Returns `Error::WouldBlock` if the process table or memory is exhausted.
+pub fn fork() -> Result<ForkResult> {
+    io::flush_all();
+    match syscall::fork()? {
+        0 => Ok(ForkResult::Child),
+        pid => Ok(ForkResult::Parent(pid)),
+    }
+}
+
+/// Copies `s` into a new string with a null terminator, checking that the result is a valid
+/// `CStr`.
+fn to_c_string(s: &str) -> Result<String> {
+    if !s.is_ascii() || s.contains('\0') {
+        return Err(Error::InvalidArgument);
+    }
+    let mut c = String::with_capacity(s.len() + 1);
+    c.push_str(s);
+    c.push('\0');
+    Ok(c)
+}
+
+/// Where a child's standard stream comes from or goes to.
+#[derive(Debug)]
+pub enum Stdio {
+    /// Use the parent's file descriptor.
+    Inherit,
+    /// Connect the stream to a new pipe. The parent's end is available from `Child`.
+    Piped,
+    /// Use an open file, e.g. for `<` or `>` redirection.
+    File(File),
+}
+
+impl From<File> for Stdio {
+    fn from(file: File) -> Self {
+        Stdio::File(file)
+    }
+}
+
+/// How one of the child's standard streams is set up after `fork`.
+enum ChildFd {
+    Inherit,
+    /// Make this file descriptor the stream, then close it.
+    Replace(File),
+}
+
+/// Builder for running a program in a child process.
+///
+/// ```ignore
+/// let output = Command::new("ls").arg("/").output()?;
+/// ```
+pub struct Command {
+    /// Program path followed by the arguments, each with a null terminator.
+    argv: Vec<String>,
+    stdin: Stdio,
+    stdout: Stdio,
+    stderr: Stdio,
+    /// First invalid argument, reported by `spawn`.
+    error: Option<Error>,
+}
+
+impl Command {
+    /// Returns a command that runs `program`. `program` is both the path passed to `exec` and the
+    /// first argument.
+    pub fn new(program: &str) -> Command {
+        let mut cmd = Command {
+            argv: Vec::new(),
+            stdin: Stdio::Inherit,
+            stdout: Stdio::Inherit,
+            stderr: Stdio::Inherit,
+            error: None,
+        };
+        cmd.arg(program);
+        cmd
+    }
+
+    /// Adds an argument.
+    pub fn arg(&mut self, arg: &str) -> &mut Self {
+        match to_c_string(arg) {
+            Ok(arg) => self.argv.push(arg),
+            Err(e) => self.error = Some(e),
+        }
+        self
+    }
+
+    /// Adds several arguments.
+    pub fn args<'a>(&mut self, args: impl IntoIterator<Item = &'a str>) -> &mut Self {
+        for arg in args {
+            self.arg(arg);
+        }
+        self
+    }
+
+    /// Sets the child's standard input.
+    pub fn stdin(&mut self, stdin: Stdio) -> &mut Self {
+        self.stdin = stdin;
+        self
+    }
+
+    /// Sets the child's standard output.
+    pub fn stdout(&mut self, stdout: Stdio) -> &mut Self {
+        self.stdout = stdout;
+        self
+    }
+
+    /// Sets the child's standard error.
+    pub fn stderr(&mut self, stderr: Stdio) -> &mut Self {
+        self.stderr = stderr;
+        self
+    }
+
+    /// Starts the program in a new process. If `exec` fails the child prints a message to
+    /// standard error and exits with status 1.
+    ///
+    /// # Errors
+    /// Returns `Error::InvalidArgument` if an argument wasn't ASCII or contained a null byte, or
+    /// the error from creating a pipe or the process.
+    pub fn spawn(&mut self) -> Result<Child> {
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+        if self.argv.len() >= xv6_defs::param::MAXARG {
+            return Err(Error::ArgListTooLong);
+        }
+
+        let mut child = Child {
+            pid: 0,
+            stdin: None,
+            stdout: None,
+            stderr: None,
+        };
+        let stdin = setup(&mut self.stdin, &mut child.stdin, true)?;
+        let stdout = setup(&mut self.stdout, &mut child.stdout, false)?;
+        let stderr = setup(&mut self.stderr, &mut child.stderr, false)?;
+
+        match fork()? {
+            ForkResult::Parent(pid) => {
+                child.pid = pid;
+                // The child's ends of any pipes are dropped here, so EOF is seen correctly.
+                Ok(child)
+            }
+            ForkResult::Child => {
+                // Parent ends of pipes must be closed so the other side sees EOF.
+                drop(child);
+                let redirected = stdin
+                    .apply(0)
+                    .and_then(|_| stdout.apply(1))
+                    .and_then(|_| stderr.apply(2));
+                if let Err(e) = redirected {
+                    crate::eprintln!("redirect failed: {}", e);
+                    exit(1)
+                }
+                let argv: Vec<CStr> = self.argv.iter().map(|a| CStr::new(a)).collect();
+                let e = exec(&argv[0], &argv);
+                crate::eprintln!("exec {} failed: {}", argv[0].chars_as_str(), e);
+                exit(1)
+            }
+        }
+    }
+
+    /// Runs the program and waits for it to exit. Returns its exit status.
+    pub fn status(&mut self) -> Result<i32> {
+        self.spawn()?.wait()
+    }
+
+    /// Runs the program with standard output captured, and waits for it to exit. Returns the
+    /// exit status and everything the program wrote to standard output.
+    pub fn output(&mut self) -> Result<Output> {
+        self.stdout = Stdio::Piped;
+        let mut child = self.spawn()?;
+        let mut stdout = Vec::new();
+        if let Some(mut out) = child.stdout.take() {
+            out.read_to_end(&mut stdout)?;
+        }
+        let status = child.wait()?;
+        Ok(Output { status, stdout })
+    }
+}
+
+/// Prepares one standard stream before forking. Returns what the child should do with the stream,
+/// and stores the parent's end of a pipe in `parent_end`. `Stdio::File` values are moved out of
+/// `stdio`, leaving `Stdio::Inherit`.
+fn setup(stdio: &mut Stdio, parent_end: &mut Option<File>, input: bool) -> Result<ChildFd> {
+    match core::mem::replace(stdio, Stdio::Inherit) {
+        Stdio::Inherit => Ok(ChildFd::Inherit),
+        Stdio::Piped => {
+            let (r, w) = fs::pipe()?;
+            if input {
+                *parent_end = Some(w);
+                Ok(ChildFd::Replace(r))
+            } else {
+                *parent_end = Some(r);
+                Ok(ChildFd::Replace(w))
+            }
+        }
+        Stdio::File(file) => Ok(ChildFd::Replace(file)),
+    }
+}
+
+impl ChildFd {
+    /// In the child, sets up the descriptor `fd`.
+    fn apply(self, fd: i32) -> Result<()> {
+        match self {
+            ChildFd::Inherit => Ok(()),
+            ChildFd::Replace(file) => redirect(fd, file),
+        }
+    }
+}
+
+/// Makes `file` the descriptor `fd`, as `user/sh.c` does for redirections. Whatever `fd` was is
+/// closed, and so is `file`'s own descriptor unless it already is `fd`.
+///
+/// # Errors
+/// Returns the error from `dup`, e.g. `Error::TooManyOpenFiles`; `fd` is then left closed.
+pub fn redirect(fd: i32, file: File) -> Result<()> {
+    if file.as_raw_fd() == fd {
+        // Already in place; keep it open.
+        file.into_raw_fd();
+        return Ok(());
+    }
+    let _ = syscall::close(fd);
+    // dup returns the lowest free descriptor, which is now fd.
+    syscall::dup(file.as_raw_fd())?;
+    Ok(())
+}
+
+/// A running child process created by `Command::spawn`.
+#[derive(Debug)]
+pub struct Child {
+    pid: i32,
+    /// Write end of the child's standard input, if it was `Stdio::Piped`.
+    pub stdin: Option<File>,
+    /// Read end of the child's standard output, if it was `Stdio::Piped`.
+    pub stdout: Option<File>,
+    /// Read end of the child's standard error, if it was `Stdio::Piped`.
+    pub stderr: Option<File>,
+}
+
+impl Child {
+    /// Returns the child's PID.
+    pub fn id(&self) -> i32 {
+        self.pid
+    }
+
+    /// Kills the child.
+    pub fn kill(&mut self) -> Result<()> {
+        kill(self.pid)
+    }
+
+    /// Waits for the child to exit and returns its exit status. The pipe to the child's standard
+    /// input is closed first so it sees EOF.
+    ///
+    /// xv6 can only wait for any child, so other children that exit in the meantime are reaped
+    /// and their statuses lost.
+    pub fn wait(&mut self) -> Result<i32> {
+        self.stdin = None;
+        loop {
+            let (pid, status) = wait()?;
+            if pid == self.pid {
+                return Ok(status);
+            }
+        }
+    }
+}
+
+/// Result of `Command::output`.
+#[derive(Debug)]
+pub struct Output {
+    /// Exit status of the program.
+    pub status: i32,
+    /// Everything the program wrote to standard output.
+    pub stdout: Vec<u8>,
+}
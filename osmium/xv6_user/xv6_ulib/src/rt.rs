@@ -0,0 +1,64 @@
+//! Process startup.
+//!
+//! `exec` starts a program at its ELF entry point with `argc` in `a0`, `argv` in `a1` and the stack
+//! pointer just below the argument strings. The linker is told to use `_start` as the entry point
+//! (see `RUST_UPROGS` in `Makefile`), which prepares the stack and calls into Rust. The Rust side
+//! wraps the arguments in `Args`, runs the program's main function registered with `entry!`, and
+//! passes its return value to `exit`, so a program can never return off the end of `main`.
+
+use crate::{exit, Args};
+use xv6_defs::c_types::*;
+
+#[cfg(target_arch = "riscv64")]
+core::arch::global_asm!(
+    ".section .text._start",
+    ".globl _start",
+    "_start:",
+    // Terminate the frame pointer chain so backtraces stop here.
+    "    li fp, 0",
+    "    li ra, 0",
+    // The calling convention requires a 16-byte aligned stack.
+    "    andi sp, sp, -16",
+    "    call __xv6_ulib_start",
+    // __xv6_ulib_start never returns.
+    "1:  j 1b",
+);
+
+extern "Rust" {
+    /// The program's main function, defined by the `entry!` macro.
+    fn __xv6_ulib_main(args: Args) -> i32;
+}
+
+/// Called by `_start` with the arguments `exec` passed to the program.
+///
+/// # Safety
+/// `argv` must refer to a valid array of C strings of length `argc`.
+#[no_mangle]
+unsafe extern "C" fn __xv6_ulib_start(argc: c_int, argv: *const *const c_char) -> ! {
+    let code = __xv6_ulib_main(Args::new(argc, argv));
+    exit(code)
+}
+
+/// Declares the main function of a user program. The function must have the signature
+/// `fn(Args) -> i32`; its return value becomes the program's exit status.
+///
+/// ```ignore
+/// use xv6_ulib::Args;
+///
+/// fn run(args: Args) -> i32 {
+///     0
+/// }
+///
+/// xv6_ulib::entry!(run);
+/// ```
+#[macro_export]
+macro_rules! entry {
+    ($main:path) => {
+        #[doc(hidden)]
+        #[no_mangle]
+        pub fn __xv6_ulib_main(args: $crate::Args) -> i32 {
+            let main: fn($crate::Args) -> i32 = $main;
+            main(args)
+        }
+    };
+}
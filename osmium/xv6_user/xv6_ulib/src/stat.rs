@@ -0,0 +1,4 @@
+//! File metadata returned by `fstat`. The definitions are shared with the kernel and host tools in
+//! `xv6_defs::stat`.
+
+pub use xv6_defs::stat::{FileType, Stat, T_DEVICE, T_DIR, T_FILE};
@@ -0,0 +1,262 @@
+//! Safe wrappers around the xv6 system calls listed in `kernel/syscall.h`.
+//!
+//! Paths are passed as `CStr` so they are guaranteed to be null terminated, and failures are
+//! returned as `Error` values decoded from the kernel's negated error numbers.
+
+use crate::ecall::*;
+use crate::error::{self, Error, Result};
+use crate::io;
+use crate::stat::Stat;
+use crate::CStr;
+use core::ptr;
+use xv6_defs::c_types::*;
+use xv6_defs::fcntl::O_RDONLY;
+use xv6_defs::param::MAXARG;
+use xv6_defs::syscall::*;
+
+/// Creates a new process that is a copy of the caller. Returns the child's PID in the parent and
+/// 0 in the child.
+///
+/// # Errors
+/// Returns `Error::WouldBlock` if the process table or memory is exhausted.
+pub fn fork() -> Result<i32> {
+    error::check(unsafe { syscall0(SYS_fork) }).map(|pid| pid as i32)
+}
+
+/// Terminates the program with exit code `code`. Buffered standard output and standard error are
+/// flushed first.
+pub fn exit(code: i32) -> ! {
+    io::flush_all();
+    unsafe {
+        syscall1(SYS_exit, code as usize);
+    }
+    loop {}
+}
+
+/// Waits for a child process to exit. Returns the child's PID and exit status.
+///
+/// # Errors
+/// Returns `Error::NoChildren` if the caller has no children.
+pub fn wait() -> Result<(i32, i32)> {
+    let mut status: c_int = 0;
+    let pid = error::check(unsafe { syscall1(SYS_wait, &mut status as *mut c_int as usize) })?;
+    Ok((pid as i32, status))
+}
+
+/// Creates a pipe. Returns the file descriptors for the read end and the write end, in that order.
+///
+/// # Errors
+/// Returns an error if the pipe couldn't be allocated or the process has no free file descriptors.
+pub fn pipe() -> Result<(i32, i32)> {
+    let mut fds: [c_int; 2] = [0; 2];
+    error::check(unsafe { syscall1(SYS_pipe, fds.as_mut_ptr() as usize) })?;
+    Ok((fds[0], fds[1]))
+}
+
+/// Writes the data in `buf` to the file descriptor `fildes`. Returns the number of bytes written.
+///
+/// # Errors
+/// Returns an error if the system call failed, e.g. `Error::BadFileDescriptor` if `fildes` is not
+/// open.
+pub fn write(fildes: i32, buf: &[u8]) -> Result<usize> {
+    let result;
+    unsafe {
+        result = syscall3(
+            SYS_write,
+            fildes as usize,
+            buf.as_ptr() as usize,
+            buf.len() as c_int as usize,
+        );
+    }
+    error::check(result)
+}
+
+/// Reads data from the file descriptor `fildes` into `buf`. Returns the number of bytes read, or 0
+/// at the end of the file.
+///
+/// # Errors
+/// Returns an error if the system call failed, e.g. `Error::BadFileDescriptor` if `fildes` is not
+/// open.
+pub fn read(fildes: i32, buf: &mut [u8]) -> Result<usize> {
+    let result;
+    unsafe {
+        result = syscall3(
+            SYS_read,
+            fildes as usize,
+            buf.as_mut_ptr() as usize,
+            buf.len() as c_int as usize,
+        );
+    }
+    error::check(result)
+}
+
+/// Release open file descriptor `fildes`.
+///
+/// # Errors
+/// Returns `Error::BadFileDescriptor` if `fildes` is not open.
+pub fn close(fildes: i32) -> Result<()> {
+    let result;
+    unsafe {
+        result = syscall1(SYS_close, fildes as usize);
+    }
+    error::check(result).map(|_| ())
+}
+
+/// Sends a kill signal to the process with PID `pid`.
+///
+/// # Errors
+/// Returns `Error::NoSuchProcess` if no process has that PID.
+pub fn kill(pid: i32) -> Result<()> {
+    error::check(unsafe { syscall1(SYS_kill, pid as usize) }).map(|_| ())
+}
+
+/// Replaces the current program with the executable at `path`, passing it the arguments `argv`.
+/// By convention `argv[0]` is the program name. Only returns if the exec failed, in which case
+/// the cause is returned.
+pub fn exec(path: &CStr, argv: &[CStr]) -> Error {
+    if argv.len() >= MAXARG {
+        return Error::ArgListTooLong;
+    }
+    let mut ptrs: [*const c_char; MAXARG] = [ptr::null(); MAXARG];
+    for (p, arg) in ptrs.iter_mut().zip(argv) {
+        *p = arg.as_bytes().as_ptr();
+    }
+    let result = unsafe {
+        syscall2(
+            SYS_exec,
+            path.as_bytes().as_ptr() as usize,
+            ptrs.as_ptr() as usize,
+        )
+    };
+    match error::check(result) {
+        Ok(_) => unreachable!("exec returned without an error"),
+        Err(e) => e,
+    }
+}
+
+/// Opens `file` with corresponding read/write `flags` from `xv6_defs::fcntl`. Returns a file
+/// descriptor.
+///
+/// # Errors
+/// Returns an error if the system call failed, e.g. `Error::NotFound` if `file` doesn't exist or
+/// `Error::TooManyOpenFiles` if the process has no free file descriptors.
+pub fn open(file: &CStr, flags: i32) -> Result<i32> {
+    let result;
+    unsafe {
+        result = syscall2(SYS_open, file.as_bytes().as_ptr() as usize, flags as usize);
+    }
+    error::check(result).map(|fd| fd as i32)
+}
+
+/// Creates a device file at `path` with the given device numbers.
+pub fn mknod(path: &CStr, major: i16, minor: i16) -> Result<()> {
+    let result = unsafe {
+        syscall3(
+            SYS_mknod,
+            path.as_bytes().as_ptr() as usize,
+            major as usize,
+            minor as usize,
+        )
+    };
+    error::check(result).map(|_| ())
+}
+
+/// Removes the directory entry `path`. The file is deleted once it has no links and isn't open.
+///
+/// # Errors
+/// Returns `Error::DirectoryNotEmpty` when removing a directory that still has entries.
+pub fn unlink(path: &CStr) -> Result<()> {
+    error::check(unsafe { syscall1(SYS_unlink, path.as_bytes().as_ptr() as usize) }).map(|_| ())
+}
+
+/// Returns metadata for the open file descriptor `fildes`.
+pub fn fstat(fildes: i32) -> Result<Stat> {
+    let mut st = Stat::default();
+    error::check(unsafe { syscall2(SYS_fstat, fildes as usize, &mut st as *mut Stat as usize) })?;
+    Ok(st)
+}
+
+/// Returns metadata for the file at `path`.
+pub fn stat(path: &CStr) -> Result<Stat> {
+    let fd = open(path, O_RDONLY)?;
+    let result = fstat(fd);
+    close(fd)?;
+    result
+}
+
+/// Creates `new` as another name for the file `old`.
+pub fn link(old: &CStr, new: &CStr) -> Result<()> {
+    let result = unsafe {
+        syscall2(
+            SYS_link,
+            old.as_bytes().as_ptr() as usize,
+            new.as_bytes().as_ptr() as usize,
+        )
+    };
+    error::check(result).map(|_| ())
+}
+
+/// Creates a new directory at `path`.
+pub fn mkdir(path: &CStr) -> Result<()> {
+    error::check(unsafe { syscall1(SYS_mkdir, path.as_bytes().as_ptr() as usize) }).map(|_| ())
+}
+
+/// Changes the current working directory to `path`.
+pub fn chdir(path: &CStr) -> Result<()> {
+    error::check(unsafe { syscall1(SYS_chdir, path.as_bytes().as_ptr() as usize) }).map(|_| ())
+}
+
+/// Returns a new file descriptor referring to the same file as `fildes`. The lowest free
+/// descriptor is used.
+pub fn dup(fildes: i32) -> Result<i32> {
+    error::check(unsafe { syscall1(SYS_dup, fildes as usize) }).map(|fd| fd as i32)
+}
+
+/// Returns the PID of the current process.
+pub fn getpid() -> i32 {
+    unsafe { syscall0(SYS_getpid) as i32 }
+}
+
+/// Grows the process's memory by `n` bytes, or shrinks it if `n` is negative. Returns the start
+/// of the new memory, i.e. the previous end of the process.
+///
+/// # Errors
+/// Returns `Error::OutOfMemory` if the memory couldn't be allocated.
+///
+/// # Safety
+/// Shrinking memory invalidates everything above the new end, including memory handed out by the
+/// heap allocator. The caller must ensure none of it is still in use.
+pub unsafe fn sbrk(n: i32) -> Result<*mut u8> {
+    let p = syscall1(SYS_sbrk, n as usize);
+    if p == -1 {
+        Err(Error::OutOfMemory)
+    } else {
+        Ok(p as *mut u8)
+    }
+}
+
+/// Pauses for `ticks` clock ticks.
+///
+/// # Errors
+/// Returns `Error::Interrupted` if the process was killed while sleeping.
+pub fn sleep(ticks: i32) -> Result<()> {
+    error::check(unsafe { syscall1(SYS_sleep, ticks as usize) }).map(|_| ())
+}
+
+/// Returns the number of clock ticks since boot.
+pub fn uptime() -> i32 {
+    unsafe { syscall0(SYS_uptime) as i32 }
+}
+
+/// Lock contention statistics used by the lab allocation tests. With `print` false, resets the
+/// test-and-set counters of every kernel lock and returns 0. With `print` true, prints the
+/// statistics for the `kmem` locks and the most contended locks, and returns the total number of
+/// test-and-set spins on the `kmem` locks.
+pub fn ntas(print: bool) -> i32 {
+    unsafe { syscall1(SYS_ntas, print as usize) as i32 }
+}
+
+/// Returns the number of free physical pages in the kernel.
+pub fn nfree() -> usize {
+    unsafe { syscall0(SYS_nfree) as usize }
+}
@@ -40,7 +40,7 @@ forktest(void)
     }
   }
 
-  if(wait(0) != -1){
+  if(wait(0) >= 0){
     print("wait got too many\n");
     exit(1);
   }
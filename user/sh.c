@@ -184,7 +184,7 @@ fork1(void)
   int pid;
 
   pid = fork();
-  if(pid == -1)
+  if(pid < 0)
     panic("fork");
   return pid;
 }
@@ -93,8 +93,8 @@ truncate2(char *s)
   int fd2 = open("truncfile", O_TRUNC|O_WRONLY);
 
   int n = write(fd1, "x", 1);
-  if(n != -1){
-    printf("%s: write returned %d, expected -1\n", s, n);
+  if(n >= 0){
+    printf("%s: write returned %d, expected an error\n", s, n);
     exit(1);
   }
 
@@ -1711,7 +1711,7 @@ forktest(char *s)
     }
   }
 
-  if(wait(0) != -1){
+  if(wait(0) >= 0){
     printf("%s: wait got too many\n", s);
     exit(1);
   }
@@ -1862,7 +1862,7 @@ sbrkfail(char *s)
       // sit around until killed
       for(;;) sleep(1000);
     }
-    if(pids[i] != -1)
+    if(pids[i] > 0)
       read(fds[0], &scratch, 1);
   }
 
@@ -1870,7 +1870,7 @@ sbrkfail(char *s)
   // we'll be able to allocate here
   c = sbrk(PGSIZE);
   for(i = 0; i < sizeof(pids)/sizeof(pids[0]); i++){
-    if(pids[i] == -1)
+    if(pids[i] < 0)
       continue;
     kill(pids[i]);
     wait(0);
@@ -1940,7 +1940,7 @@ validatetest(char *s)
   hi = 1100*1024;
   for(p = 0; p <= (uint)hi; p += PGSIZE){
     // try to crash the kernel by passing in a bad string pointer
-    if(link("nosuchfile", (char*)p) != -1){
+    if(link("nosuchfile", (char*)p) >= 0){
       printf("%s: link should not succeed\n", s);
       exit(1);
     }